@@ -0,0 +1,1138 @@
+//! Sample-level helpers shared by the recorder and the offline audio tools.
+
+use flacenc::component::BitRepr;
+use flacenc::error::Verify;
+use rustfft::num_complex::Complex;
+use rustfft::FftPlanner;
+use std::fs;
+use std::ops::Range;
+use std::path::Path;
+
+/// Decoded WAV contents as interleaved samples normalized to -1.0..=1.0.
+pub struct WavAudio {
+    pub samples: Vec<f32>,
+    pub sample_rate: u32,
+    pub channels: u16,
+}
+
+pub fn read_wav(path: &Path) -> Result<WavAudio, String> {
+    let mut reader = hound::WavReader::open(path)
+        .map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+    let spec = reader.spec();
+
+    let samples = match spec.sample_format {
+        hound::SampleFormat::Float => reader
+            .samples::<f32>()
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| e.to_string())?,
+        hound::SampleFormat::Int => {
+            let scale = (1i64 << (spec.bits_per_sample.max(1) - 1)) as f32;
+            reader
+                .samples::<i32>()
+                .map(|sample| sample.map(|value| value as f32 / scale))
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|e| e.to_string())?
+        }
+    };
+
+    Ok(WavAudio {
+        samples,
+        sample_rate: spec.sample_rate,
+        channels: spec.channels,
+    })
+}
+
+/// Length of the frames whose energies give [`AudioStats::noise_floor_dbfs`].
+const ANALYSIS_FRAME_MS: u32 = 50;
+/// Percentile of frame energies taken as the noise floor: the quietest stretch
+/// that isn't a one-off dropout.
+const NOISE_FLOOR_PERCENTILE: usize = 10;
+/// Normalized level counted as clipped; the largest positive 16-bit sample
+/// only reaches 32767/32768.
+const CLIP_LEVEL: f32 = 0.9999;
+
+#[derive(serde::Serialize, Clone)]
+pub struct AudioStats {
+    pub peak_dbfs: f32,
+    pub rms_dbfs: f32,
+    pub noise_floor_dbfs: f32,
+    /// Samples at or beyond full scale, across all channels.
+    pub clip_count: u64,
+    /// Mean sample value as a fraction of full scale; far from 0 means the
+    /// input has a DC bias.
+    pub dc_offset: f32,
+    pub duration_ms: u64,
+}
+
+/// Computes [`AudioStats`] over interleaved normalized samples in one pass.
+pub fn analyze(samples: &[f32], channels: u16, sample_rate: u32) -> AudioStats {
+    let channels = channels.max(1) as usize;
+    let frame_len = ((sample_rate * ANALYSIS_FRAME_MS / 1000) as usize).max(1) * channels;
+    let mut peak = 0.0f32;
+    let mut sum = 0.0f64;
+    let mut sum_squares = 0.0f64;
+    let mut clip_count = 0;
+    let mut frame_energies = Vec::with_capacity(samples.len() / frame_len + 1);
+    for frame in samples.chunks(frame_len) {
+        let mut frame_squares = 0.0f64;
+        for &sample in frame {
+            let abs = sample.abs();
+            peak = peak.max(abs);
+            if abs >= CLIP_LEVEL {
+                clip_count += 1;
+            }
+            sum += sample as f64;
+            frame_squares += sample as f64 * sample as f64;
+        }
+        sum_squares += frame_squares;
+        frame_energies.push(frame_squares / frame.len() as f64);
+    }
+
+    let count = samples.len().max(1) as f64;
+    frame_energies.sort_by(f64::total_cmp);
+    let noise_floor = frame_energies
+        .get(frame_energies.len() * NOISE_FLOOR_PERCENTILE / 100)
+        .copied()
+        .unwrap_or(0.0);
+    AudioStats {
+        peak_dbfs: linear_to_dbfs(peak),
+        rms_dbfs: linear_to_dbfs((sum_squares / count).sqrt() as f32),
+        noise_floor_dbfs: linear_to_dbfs(noise_floor.sqrt() as f32),
+        clip_count,
+        dc_offset: (sum / count) as f32,
+        duration_ms: if sample_rate == 0 {
+            0
+        } else {
+            (samples.len() / channels) as u64 * 1000 / sample_rate as u64
+        },
+    }
+}
+
+/// Lowest level reported, standing in for digital silence.
+pub const DBFS_FLOOR: f32 = -100.0;
+
+/// Converts a linear 0..1 amplitude to dBFS, clamped to [`DBFS_FLOOR`].
+pub fn linear_to_dbfs(linear: f32) -> f32 {
+    if linear <= 0.0 {
+        return DBFS_FLOOR;
+    }
+    (20.0 * linear.log10()).max(DBFS_FLOOR)
+}
+
+/// -3 dB, the usual level for center and surround channels in a downmix.
+const MINUS_3_DB: f32 = std::f32::consts::FRAC_1_SQRT_2;
+
+/// Mono downmix weights for common surround layouts in WAVE channel order
+/// (L, R, C, LFE, Ls, Rs, ...): fronts at full level, center and surrounds
+/// at -3 dB, LFE dropped. `None` means every channel is weighted equally,
+/// which is also what mono and stereo use.
+pub const fn downmix_weights(channels: usize) -> Option<&'static [f32]> {
+    match channels {
+        // L R C
+        3 => Some(&[1.0, 1.0, MINUS_3_DB]),
+        // L R Ls Rs
+        4 => Some(&[1.0, 1.0, MINUS_3_DB, MINUS_3_DB]),
+        // L R C Ls Rs
+        5 => Some(&[1.0, 1.0, MINUS_3_DB, MINUS_3_DB, MINUS_3_DB]),
+        // 5.1: L R C LFE Ls Rs
+        6 => Some(&[1.0, 1.0, MINUS_3_DB, 0.0, MINUS_3_DB, MINUS_3_DB]),
+        // 7.1: L R C LFE Lb Rb Ls Rs
+        8 => Some(&[
+            1.0, 1.0, MINUS_3_DB, 0.0, MINUS_3_DB, MINUS_3_DB, MINUS_3_DB, MINUS_3_DB,
+        ]),
+        _ => None,
+    }
+}
+
+/// Sum of [`downmix_weights`] for `channels`, used to keep the downmix at the
+/// same level as its inputs.
+pub fn downmix_weight_sum(channels: usize) -> f32 {
+    match downmix_weights(channels) {
+        Some(weights) => weights.iter().sum(),
+        None => channels as f32,
+    }
+}
+
+/// Mixes each interleaved frame into a single channel with
+/// [`downmix_weights`].
+pub fn downmix_to_mono(samples: &[f32], channels: u16) -> Vec<f32> {
+    let channels = channels.max(1) as usize;
+    if channels == 1 {
+        return samples.to_vec();
+    }
+    let weights = downmix_weights(channels);
+    let weight_sum = downmix_weight_sum(channels);
+    samples
+        .chunks_exact(channels)
+        .map(|frame| {
+            let sum: f32 = match weights {
+                Some(weights) => frame.iter().zip(weights).map(|(s, w)| s * w).sum(),
+                None => frame.iter().sum(),
+            };
+            sum / weight_sum
+        })
+        .collect()
+}
+
+/// Resamples a mono buffer with linear interpolation.
+///
+/// This is cheap and good enough for speech, but it does not low-pass before
+/// decimating, so content above the new Nyquist frequency can alias.
+pub fn resample_linear(samples: &[f32], from_rate: u32, to_rate: u32) -> Vec<f32> {
+    if from_rate == to_rate || from_rate == 0 || to_rate == 0 || samples.is_empty() {
+        return samples.to_vec();
+    }
+
+    let ratio = from_rate as f64 / to_rate as f64;
+    let output_len = (samples.len() as u64 * to_rate as u64 / from_rate as u64) as usize;
+
+    (0..output_len)
+        .map(|index| {
+            let position = index as f64 * ratio;
+            let base = position.floor() as usize;
+            let fraction = (position - base as f64) as f32;
+            let current = samples[base];
+            let next = samples.get(base + 1).copied().unwrap_or(current);
+            current + (next - current) * fraction
+        })
+        .collect()
+}
+
+/// Resamples interleaved 16-bit audio channel by channel with
+/// [`resample_linear`].
+pub fn resample_interleaved(
+    samples: &[i16],
+    channels: u16,
+    from_rate: u32,
+    to_rate: u32,
+) -> Vec<i16> {
+    let channels = channels.max(1) as usize;
+    let resampled: Vec<Vec<f32>> = (0..channels)
+        .map(|channel| {
+            let plane: Vec<f32> = samples
+                .iter()
+                .skip(channel)
+                .step_by(channels)
+                .map(|&sample| sample as f32)
+                .collect();
+            resample_linear(&plane, from_rate, to_rate)
+        })
+        .collect();
+
+    let frames = resampled.iter().map(Vec::len).min().unwrap_or(0);
+    let mut output = Vec::with_capacity(frames * channels);
+    for frame in 0..frames {
+        for plane in &resampled {
+            output.push(plane[frame].round().clamp(i16::MIN as f32, i16::MAX as f32) as i16);
+        }
+    }
+    output
+}
+
+/// Adds `other` onto `base` sample by sample, clamping each sum to the i16
+/// range. `base` grows when `other` is longer.
+pub fn mix_into(base: &mut Vec<i16>, other: &[i16]) {
+    if other.len() > base.len() {
+        base.resize(other.len(), 0);
+    }
+    for (sample, &addend) in base.iter_mut().zip(other) {
+        *sample = (*sample as i32 + addend as i32).clamp(i16::MIN as i32, i16::MAX as i32) as i16;
+    }
+}
+
+/// Losslessly encodes interleaved 16-bit samples to a FLAC file.
+pub fn encode_flac(
+    path: &Path,
+    samples: &[i16],
+    sample_rate: u32,
+    channels: u16,
+) -> Result<(), String> {
+    let config = flacenc::config::Encoder::default()
+        .into_verified()
+        .map_err(|(_, e)| format!("Invalid FLAC encoder config: {:?}", e))?;
+    let samples: Vec<i32> = samples.iter().map(|&sample| sample as i32).collect();
+    let source = flacenc::source::MemSource::from_samples(
+        &samples,
+        channels as usize,
+        16,
+        sample_rate as usize,
+    );
+    let stream = flacenc::encode_with_fixed_block_size(&config, source, config.block_size)
+        .map_err(|e| format!("FLAC encoding failed: {:?}", e))?;
+
+    let mut sink = flacenc::bitsink::ByteSink::new();
+    stream
+        .write(&mut sink)
+        .map_err(|e| format!("FLAC encoding failed: {:?}", e))?;
+    fs::write(path, sink.as_slice())
+        .map_err(|e| format!("Failed to write recording to {}: {}", path.display(), e))
+}
+
+/// Sample rate G.711 telephony audio runs at.
+pub const ULAW_SAMPLE_RATE: u32 = 8_000;
+
+/// `WAVE_FORMAT_MULAW` format tag.
+const WAVE_FORMAT_MULAW: u16 = 0x0007;
+
+/// Encodes one 16-bit sample to G.711 μ-law, following the ITU reference
+/// encoder: bias, find the segment, keep four mantissa bits, invert.
+pub fn linear_to_ulaw(sample: i16) -> u8 {
+    const BIAS: i32 = 0x84;
+    const CLIP: i32 = 32_635;
+    let value = sample as i32;
+    let sign = if value < 0 { 0x80 } else { 0 };
+    let magnitude = value.abs().min(CLIP) + BIAS;
+    let exponent = (magnitude >> 7).ilog2() as i32;
+    let mantissa = (magnitude >> (exponent + 3)) & 0x0F;
+    !(sign | (exponent << 4) | mantissa) as u8
+}
+
+/// Writes mono 16-bit samples as a μ-law WAV file. hound only writes PCM and
+/// float, so the RIFF chunks are assembled here; non-PCM formats get a `fact`
+/// chunk with the sample count.
+pub fn encode_ulaw_wav(path: &Path, samples: &[i16], sample_rate: u32) -> Result<(), String> {
+    let data: Vec<u8> = samples
+        .iter()
+        .map(|&sample| linear_to_ulaw(sample))
+        .collect();
+    let data_len = u32::try_from(data.len())
+        .ok()
+        .filter(|len| *len <= u32::MAX - 64)
+        .ok_or_else(|| format!("{} would be too large for a WAV file", path.display()))?;
+
+    let mut file = Vec::with_capacity(data.len() + 58);
+    file.extend_from_slice(b"RIFF");
+    // WAVE, fmt (8 + 18), fact (8 + 4), data (8 + len, padded to even).
+    let riff_len = 4 + 26 + 12 + 8 + data_len + data_len % 2;
+    file.extend_from_slice(&riff_len.to_le_bytes());
+    file.extend_from_slice(b"WAVE");
+
+    file.extend_from_slice(b"fmt ");
+    file.extend_from_slice(&18u32.to_le_bytes());
+    file.extend_from_slice(&WAVE_FORMAT_MULAW.to_le_bytes());
+    file.extend_from_slice(&1u16.to_le_bytes()); // channels
+    file.extend_from_slice(&sample_rate.to_le_bytes());
+    file.extend_from_slice(&sample_rate.to_le_bytes()); // bytes per second
+    file.extend_from_slice(&1u16.to_le_bytes()); // block align
+    file.extend_from_slice(&8u16.to_le_bytes()); // bits per sample
+    file.extend_from_slice(&0u16.to_le_bytes()); // no extension
+
+    file.extend_from_slice(b"fact");
+    file.extend_from_slice(&4u32.to_le_bytes());
+    file.extend_from_slice(&data_len.to_le_bytes());
+
+    file.extend_from_slice(b"data");
+    file.extend_from_slice(&data_len.to_le_bytes());
+    file.extend(data);
+    if data_len % 2 == 1 {
+        file.push(0);
+    }
+
+    fs::write(path, file).map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+}
+
+/// Sample rates the Opus encoder accepts. Anything else has to be resampled
+/// first.
+pub const OPUS_SAMPLE_RATES: [u32; 5] = [8_000, 12_000, 16_000, 24_000, 48_000];
+
+/// Returned when Opus output is requested from a build without the `opus`
+/// feature.
+pub const OPUS_UNAVAILABLE: &str = "Opus output is unavailable: built without the `opus` feature";
+
+/// Encodes interleaved 16-bit mono or stereo samples to an Ogg Opus file at
+/// `bitrate` bits per second, in 20 ms frames. `sample_rate` must be one of
+/// [`OPUS_SAMPLE_RATES`].
+#[cfg(feature = "opus")]
+pub fn encode_opus(
+    path: &Path,
+    samples: &[i16],
+    sample_rate: u32,
+    channels: u16,
+    bitrate: u32,
+) -> Result<(), String> {
+    use ogg::writing::{PacketWriteEndInfo, PacketWriter};
+    use std::io::{BufWriter, Write};
+
+    // A file holds a single logical stream, so any serial number will do.
+    const SERIAL: u32 = 1;
+
+    let opus_channels = match channels {
+        1 => opus::Channels::Mono,
+        2 => opus::Channels::Stereo,
+        _ => {
+            return Err(format!(
+                "Opus output supports 1 or 2 channels, not {}",
+                channels
+            ))
+        }
+    };
+    if !OPUS_SAMPLE_RATES.contains(&sample_rate) {
+        return Err(format!(
+            "Opus does not support a {} Hz sample rate",
+            sample_rate
+        ));
+    }
+    let encoder_error = |e: opus::Error| format!("Opus encoding failed: {}", e);
+    let mut encoder = opus::Encoder::new(sample_rate, opus_channels, opus::Application::Voip)
+        .map_err(encoder_error)?;
+    encoder
+        .set_bitrate(opus::Bitrate::Bits(bitrate as i32))
+        .map_err(encoder_error)?;
+    let lookahead = encoder.get_lookahead().map_err(encoder_error)?.max(0) as u64;
+
+    // Granule positions count samples per channel at 48 kHz whatever the input
+    // rate, and include the encoder delay the decoder skips.
+    let granule_scale = (48_000 / sample_rate) as u64;
+    let pre_skip = lookahead * granule_scale;
+    let channels = channels as usize;
+    let input_frames = (samples.len() / channels) as u64;
+    let frame_size = (sample_rate / 50) as u64;
+    // Keep feeding silence until the delayed tail of the input is out.
+    let packet_count = (input_frames + lookahead).div_ceil(frame_size).max(1);
+    let end_granule = pre_skip + input_frames * granule_scale;
+
+    let write_error =
+        |e: std::io::Error| format!("Failed to write recording to {}: {}", path.display(), e);
+    let file = fs::File::create(path).map_err(write_error)?;
+    let mut writer = PacketWriter::new(BufWriter::new(file));
+
+    let mut head = b"OpusHead".to_vec();
+    head.push(1);
+    head.push(channels as u8);
+    head.extend_from_slice(&(pre_skip as u16).to_le_bytes());
+    head.extend_from_slice(&sample_rate.to_le_bytes());
+    // Output gain, then channel mapping family 0 (mono or stereo).
+    head.extend_from_slice(&0i16.to_le_bytes());
+    head.push(0);
+    writer
+        .write_packet(head, SERIAL, PacketWriteEndInfo::EndPage, 0)
+        .map_err(write_error)?;
+
+    let vendor = opus::version();
+    let mut tags = b"OpusTags".to_vec();
+    tags.extend_from_slice(&(vendor.len() as u32).to_le_bytes());
+    tags.extend_from_slice(vendor.as_bytes());
+    tags.extend_from_slice(&0u32.to_le_bytes());
+    writer
+        .write_packet(tags, SERIAL, PacketWriteEndInfo::EndPage, 0)
+        .map_err(write_error)?;
+
+    let frame_len = frame_size as usize * channels;
+    let mut frame = vec![0i16; frame_len];
+    // The largest packet libopus recommends budgeting for.
+    let mut packet = vec![0u8; 4000];
+    for index in 0..packet_count {
+        let start = (index as usize * frame_len).min(samples.len());
+        let end = (start + frame_len).min(samples.len());
+        frame.fill(0);
+        frame[..end - start].copy_from_slice(&samples[start..end]);
+        let len = encoder.encode(&frame, &mut packet).map_err(encoder_error)?;
+
+        let (end_info, granule) = if index + 1 == packet_count {
+            (PacketWriteEndInfo::EndStream, end_granule)
+        } else {
+            (
+                PacketWriteEndInfo::NormalPacket,
+                (index + 1) * frame_size * granule_scale,
+            )
+        };
+        writer
+            .write_packet(packet[..len].to_vec(), SERIAL, end_info, granule)
+            .map_err(write_error)?;
+    }
+    writer.into_inner().flush().map_err(write_error)
+}
+
+#[cfg(not(feature = "opus"))]
+pub fn encode_opus(
+    _path: &Path,
+    _samples: &[i16],
+    _sample_rate: u32,
+    _channels: u16,
+    _bitrate: u32,
+) -> Result<(), String> {
+    Err(OPUS_UNAVAILABLE.to_string())
+}
+
+/// First-order high-pass filter for removing DC offset and low rumble from
+/// interleaved audio, one filter state per channel.
+#[derive(Default)]
+pub struct HighPassFilter {
+    // (previous input, previous output) for each channel.
+    history: Vec<(f32, f32)>,
+}
+
+impl HighPassFilter {
+    /// Clears the filter history and sizes it for `channels`.
+    pub fn reset(&mut self, channels: usize) {
+        self.history.clear();
+        self.history.resize(channels, (0.0, 0.0));
+    }
+
+    /// Filters one sample of `channel` with a coefficient from
+    /// [`highpass_coefficient`].
+    pub fn process(&mut self, channel: usize, sample: f32, coefficient: f32) -> f32 {
+        let Some((previous_input, previous_output)) = self.history.get_mut(channel) else {
+            return sample;
+        };
+        let output = coefficient * (*previous_output + sample - *previous_input);
+        *previous_input = sample;
+        *previous_output = output;
+        output
+    }
+}
+
+/// RC coefficient of a first-order high-pass with the given cutoff.
+pub fn highpass_coefficient(cutoff_hz: f32, sample_rate: u32) -> f32 {
+    let rc = 1.0 / (2.0 * std::f32::consts::PI * cutoff_hz);
+    let dt = 1.0 / sample_rate.max(1) as f32;
+    rc / (rc + dt)
+}
+
+/// Scales 16-bit samples so the loudest one lands on `target_dbfs`, clamping
+/// anything that would clip. Returns the linear gain applied, or `None` for a
+/// silent buffer, which is left as is.
+pub fn normalize_peak(samples: &mut [i16], target_dbfs: f32) -> Option<f32> {
+    let peak = samples
+        .iter()
+        .map(|&sample| (sample as i32).unsigned_abs())
+        .max()
+        .unwrap_or(0);
+    if peak == 0 {
+        return None;
+    }
+
+    let target = dbfs_to_linear(target_dbfs) * i16::MAX as f32;
+    let gain = target / peak as f32;
+    for sample in samples.iter_mut() {
+        *sample = (*sample as f32 * gain)
+            .round()
+            .clamp(i16::MIN as f32, i16::MAX as f32) as i16;
+    }
+    Some(gain)
+}
+
+/// Scales interleaved 16-bit samples by `gain`, running a [`Limiter`] over
+/// each frame so peaks pushed past the threshold are held down instead of
+/// clipping.
+pub fn apply_gain_limited(samples: &mut [i16], channels: u16, gain: f32, params: &LimiterParams) {
+    let mut limiter = Limiter::default();
+    for frame in samples.chunks_mut(channels.max(1) as usize) {
+        let frame_max = frame
+            .iter()
+            .map(|&sample| (sample as f32 / i16::MAX as f32 * gain).abs())
+            .fold(0.0f32, f32::max);
+        let frame_gain = gain * limiter.process(frame_max, params);
+        for sample in frame.iter_mut() {
+            *sample = (*sample as f32 * frame_gain)
+                .round()
+                .clamp(i16::MIN as f32, i16::MAX as f32) as i16;
+        }
+    }
+}
+
+/// Gating block length and hop for integrated loudness, per BS.1770.
+const LOUDNESS_BLOCK_MS: usize = 400;
+const LOUDNESS_HOP_MS: usize = 100;
+const LOUDNESS_ABSOLUTE_GATE: f64 = -70.0;
+const LOUDNESS_RELATIVE_GATE: f64 = -10.0;
+
+/// Direct form I biquad with `a0` normalized to 1.
+struct Biquad {
+    b: [f64; 3],
+    a: [f64; 2],
+    x: [f64; 2],
+    y: [f64; 2],
+}
+
+impl Biquad {
+    fn new(b: [f64; 3], a: [f64; 2]) -> Self {
+        Self {
+            b,
+            a,
+            x: [0.0; 2],
+            y: [0.0; 2],
+        }
+    }
+
+    fn process(&mut self, x: f64) -> f64 {
+        let y = self.b[0] * x + self.b[1] * self.x[0] + self.b[2] * self.x[1]
+            - self.a[0] * self.y[0]
+            - self.a[1] * self.y[1];
+        self.x = [x, self.x[0]];
+        self.y = [y, self.y[0]];
+        y
+    }
+}
+
+/// The two K-weighting stages of BS.1770, a high shelf modelling the head
+/// followed by a high-pass, with coefficients derived for `sample_rate` so
+/// rates other than 48 kHz get the same response.
+fn k_weighting(sample_rate: u32) -> [Biquad; 2] {
+    let rate = sample_rate as f64;
+
+    let k = (std::f64::consts::PI * 1_681.974_450_955_533 / rate).tan();
+    let q = 0.707_175_236_955_419_6;
+    let vh = 10f64.powf(3.999_843_853_973_347 / 20.0);
+    let vb = vh.powf(0.499_666_774_154_541_6);
+    let a0 = 1.0 + k / q + k * k;
+    let shelf = Biquad::new(
+        [
+            (vh + vb * k / q + k * k) / a0,
+            2.0 * (k * k - vh) / a0,
+            (vh - vb * k / q + k * k) / a0,
+        ],
+        [2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+    );
+
+    let k = (std::f64::consts::PI * 38.135_470_876_024_44 / rate).tan();
+    let q = 0.500_327_037_323_877_3;
+    let a0 = 1.0 + k / q + k * k;
+    let highpass = Biquad::new(
+        [1.0, -2.0, 1.0],
+        [2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+    );
+
+    [shelf, highpass]
+}
+
+fn block_loudness(mean_square: f64) -> f64 {
+    -0.691 + 10.0 * mean_square.log10()
+}
+
+/// Integrated loudness in LUFS (ITU-R BS.1770) of the mono downmix of
+/// interleaved 16-bit samples. Returns `None` when the recording is shorter
+/// than one 400 ms block or every block falls below the absolute gate.
+pub fn integrated_loudness(samples: &[i16], channels: u16, sample_rate: u32) -> Option<f32> {
+    let hop = sample_rate as usize * LOUDNESS_HOP_MS / 1000;
+    let hops_per_block = LOUDNESS_BLOCK_MS / LOUDNESS_HOP_MS;
+    if hop == 0 {
+        return None;
+    }
+
+    let normalized: Vec<f32> = samples
+        .iter()
+        .map(|&sample| sample as f32 / i16::MAX as f32)
+        .collect();
+    let mono = downmix_to_mono(&normalized, channels);
+    // Energy of the K-weighted signal per hop; each gating block spans
+    // `hops_per_block` consecutive hops.
+    let [mut shelf, mut highpass] = k_weighting(sample_rate);
+    let hop_energy: Vec<f64> = mono
+        .chunks_exact(hop)
+        .map(|chunk| {
+            chunk
+                .iter()
+                .map(|&sample| {
+                    let weighted = highpass.process(shelf.process(sample as f64));
+                    weighted * weighted
+                })
+                .sum()
+        })
+        .collect();
+    let block_len = (hop * hops_per_block) as f64;
+    let blocks: Vec<f64> = hop_energy
+        .windows(hops_per_block)
+        .map(|window| window.iter().sum::<f64>() / block_len)
+        .filter(|&mean_square| block_loudness(mean_square) > LOUDNESS_ABSOLUTE_GATE)
+        .collect();
+    if blocks.is_empty() {
+        return None;
+    }
+
+    let mean = |blocks: &[f64]| blocks.iter().sum::<f64>() / blocks.len() as f64;
+    let relative_gate = block_loudness(mean(&blocks)) + LOUDNESS_RELATIVE_GATE;
+    let gated: Vec<f64> = blocks
+        .into_iter()
+        .filter(|&mean_square| block_loudness(mean_square) > relative_gate)
+        .collect();
+    Some(block_loudness(mean(&gated)) as f32)
+}
+
+/// Interleaved sample range from the first to the last frame with a channel at
+/// or above `threshold_dbfs`, widened by `padding_frames` on both sides and
+/// kept on frame boundaries. `None` if no frame reaches the threshold.
+pub fn non_silent_range(
+    samples: &[i16],
+    channels: u16,
+    threshold_dbfs: f32,
+    padding_frames: usize,
+) -> Option<Range<usize>> {
+    let channels = channels.max(1) as usize;
+    let threshold = dbfs_to_linear(threshold_dbfs) * i16::MAX as f32;
+    let loud = |frame: &[i16]| {
+        frame
+            .iter()
+            .any(|&sample| (sample as i32).unsigned_abs() as f32 >= threshold)
+    };
+    let frames: Vec<&[i16]> = samples.chunks_exact(channels).collect();
+    let first = frames.iter().position(|frame| loud(frame))?;
+    let last = frames.iter().rposition(|frame| loud(frame))?;
+    let start = first.saturating_sub(padding_frames);
+    let end = (last + 1 + padding_frames).min(frames.len());
+    Some(start * channels..end * channels)
+}
+
+/// Reduces interleaved 16-bit audio to at most `max_points` values in
+/// -1..=1 for drawing. Each point is the sample with the largest magnitude in
+/// its bucket of frames, sign kept. `None` keeps one point per frame.
+pub fn peak_buckets(samples: &[i16], channels: u16, max_points: Option<usize>) -> Vec<f32> {
+    let channels = channels.max(1) as usize;
+    let frames = samples.len() / channels;
+    let points = max_points.unwrap_or(frames).clamp(1, frames.max(1));
+    let frames_per_point = frames.div_ceil(points).max(1);
+    samples[..frames * channels]
+        .chunks(frames_per_point * channels)
+        .map(|bucket| {
+            let loudest = bucket
+                .iter()
+                .copied()
+                .max_by_key(|&sample| (sample as i32).unsigned_abs())
+                .unwrap_or(0);
+            (loudest as f32 / i16::MAX as f32).max(-1.0)
+        })
+        .collect()
+}
+
+/// Converts interleaved L/R frames to mid/side in place: M = (L + R) / 2 and
+/// S = (L - R) / 2. The halving keeps both in range, and the original is
+/// recovered exactly up to rounding with L = M + S, R = M - S.
+pub fn stereo_to_mid_side(samples: &mut [i16]) {
+    for frame in samples.chunks_exact_mut(2) {
+        let (left, right) = (frame[0] as i32, frame[1] as i32);
+        frame[0] = ((left + right) / 2) as i16;
+        frame[1] = ((left - right) / 2) as i16;
+    }
+}
+
+/// Leading stretch of a recording taken as the noise profile for
+/// [`spectral_denoise`]; callers are expected to start recording before
+/// anyone speaks.
+pub const DENOISE_PROFILE_MS: u32 = 300;
+const DENOISE_FRAME_LEN: usize = 1024;
+/// How much of the noise estimate is removed. Slightly over 1 keeps the
+/// residual hiss down at the cost of some musical noise.
+const DENOISE_OVERSUBTRACTION: f32 = 1.5;
+/// Fraction of each bin's magnitude that is always kept.
+const DENOISE_SPECTRAL_FLOOR: f32 = 0.05;
+
+/// Reduces stationary noise by spectral subtraction. The average magnitude
+/// spectrum of the first [`DENOISE_PROFILE_MS`] is subtracted from every
+/// frame, channel by channel, and the audio is rebuilt by overlap-add.
+/// Returns false, leaving the samples alone, when the recording is too short
+/// to estimate the profile.
+pub fn spectral_denoise(samples: &mut [i16], channels: u16, sample_rate: u32) -> bool {
+    let channels = channels.max(1) as usize;
+    let frames = samples.len() / channels;
+    let profile_len = (sample_rate as u64 * DENOISE_PROFILE_MS as u64 / 1000) as usize;
+    if profile_len < DENOISE_FRAME_LEN || frames < profile_len {
+        return false;
+    }
+
+    let hop = DENOISE_FRAME_LEN / 2;
+    // Periodic Hann: at 50% overlap the windows sum to one, so overlap-add
+    // needs no synthesis window.
+    let window: Vec<f32> = (0..DENOISE_FRAME_LEN)
+        .map(|n| {
+            let phase = 2.0 * std::f32::consts::PI * n as f32 / DENOISE_FRAME_LEN as f32;
+            0.5 - 0.5 * phase.cos()
+        })
+        .collect();
+    let mut planner = FftPlanner::<f32>::new();
+    let forward = planner.plan_fft_forward(DENOISE_FRAME_LEN);
+    let inverse = planner.plan_fft_inverse(DENOISE_FRAME_LEN);
+    let spectrum = |frame: &[f32]| -> Vec<Complex<f32>> {
+        let mut buffer: Vec<Complex<f32>> = frame
+            .iter()
+            .zip(&window)
+            .map(|(&sample, &weight)| Complex::new(sample * weight, 0.0))
+            .collect();
+        forward.process(&mut buffer);
+        buffer
+    };
+
+    for channel in 0..channels {
+        let plane: Vec<f32> = samples
+            .iter()
+            .skip(channel)
+            .step_by(channels)
+            .map(|&sample| sample as f32)
+            .collect();
+
+        let mut noise = vec![0.0f32; DENOISE_FRAME_LEN];
+        let mut profile_frames = 0;
+        for start in (0..=profile_len - DENOISE_FRAME_LEN).step_by(hop) {
+            for (bin, value) in spectrum(&plane[start..start + DENOISE_FRAME_LEN])
+                .iter()
+                .enumerate()
+            {
+                noise[bin] += value.norm();
+            }
+            profile_frames += 1;
+        }
+        for magnitude in &mut noise {
+            *magnitude *= DENOISE_OVERSUBTRACTION / profile_frames as f32;
+        }
+
+        // Pad by a hop on each side so every sample is covered by two frames.
+        let mut padded = vec![0.0f32; hop + frames + DENOISE_FRAME_LEN];
+        padded[hop..hop + frames].copy_from_slice(&plane);
+        let mut output = vec![0.0f32; padded.len()];
+        for start in (0..=padded.len() - DENOISE_FRAME_LEN).step_by(hop) {
+            let mut buffer = spectrum(&padded[start..start + DENOISE_FRAME_LEN]);
+            for (value, &noise) in buffer.iter_mut().zip(&noise) {
+                let magnitude = value.norm();
+                if magnitude > 0.0 {
+                    let cleaned = (magnitude - noise).max(magnitude * DENOISE_SPECTRAL_FLOOR);
+                    *value *= cleaned / magnitude;
+                }
+            }
+            inverse.process(&mut buffer);
+            for (out, value) in output[start..start + DENOISE_FRAME_LEN]
+                .iter_mut()
+                .zip(&buffer)
+            {
+                *out += value.re / DENOISE_FRAME_LEN as f32;
+            }
+        }
+
+        for (sample, &value) in samples
+            .iter_mut()
+            .skip(channel)
+            .step_by(channels)
+            .zip(&output[hop..hop + frames])
+        {
+            *sample = value.round().clamp(i16::MIN as f32, i16::MAX as f32) as i16;
+        }
+    }
+    true
+}
+
+/// Converts a dBFS level back to a linear 0..1 amplitude.
+pub fn dbfs_to_linear(dbfs: f32) -> f32 {
+    10f32.powf(dbfs / 20.0)
+}
+
+/// One-pole smoothing coefficient for a time constant in milliseconds, applied
+/// once per frame.
+pub fn smoothing_coefficient(time_ms: f32, sample_rate: u32) -> f32 {
+    let frames = time_ms / 1000.0 * sample_rate as f32;
+    if frames <= 0.0 {
+        return 0.0;
+    }
+    (-1.0 / frames).exp()
+}
+
+/// Tuning for [`AutomaticGain`], with levels as linear amplitudes and the
+/// time constants as [`smoothing_coefficient`]s.
+pub struct AgcParams {
+    pub target: f32,
+    pub noise_floor: f32,
+    pub attack: f32,
+    pub release: f32,
+    pub max_gain: f32,
+}
+
+/// Automatic gain control: tracks a smoothed RMS level of the input and the
+/// gain that brings it to the target.
+pub struct AutomaticGain {
+    level: f32,
+    gain: f32,
+}
+
+impl Default for AutomaticGain {
+    fn default() -> Self {
+        Self {
+            level: 0.0,
+            gain: 1.0,
+        }
+    }
+}
+
+impl AutomaticGain {
+    pub fn gain(&self) -> f32 {
+        self.gain
+    }
+
+    /// Feeds the mean power of one frame, measured before the AGC gain. Frames
+    /// below the noise floor leave the estimate alone so pauses in speech do
+    /// not get pumped up to the target level.
+    pub fn update(&mut self, power: f32, params: &AgcParams) {
+        let rms = power.sqrt();
+        if rms < params.noise_floor {
+            return;
+        }
+        let coefficient = if rms > self.level {
+            params.attack
+        } else {
+            params.release
+        };
+        self.level = coefficient * self.level + (1.0 - coefficient) * rms;
+        if self.level > 0.0 {
+            self.gain = (params.target / self.level).min(params.max_gain);
+        }
+    }
+}
+
+/// Tuning for [`Limiter`]: the ceiling as a linear amplitude and the release
+/// as a [`smoothing_coefficient`].
+pub struct LimiterParams {
+    pub threshold: f32,
+    pub release: f32,
+}
+
+/// Peak limiter with instant attack and smoothed release. A frame that would
+/// exceed the threshold is scaled down right onto it, and the gain then
+/// recovers gradually, so loud transients are attenuated instead of clipped
+/// and the gain never jumps back up.
+pub struct Limiter {
+    gain: f32,
+}
+
+impl Default for Limiter {
+    fn default() -> Self {
+        Self { gain: 1.0 }
+    }
+}
+
+impl Limiter {
+    /// Takes the peak of one frame before limiting and returns the gain to
+    /// apply to it.
+    pub fn process(&mut self, peak: f32, params: &LimiterParams) -> f32 {
+        let target = if peak > params.threshold {
+            params.threshold / peak
+        } else {
+            1.0
+        };
+        self.gain = if target < self.gain {
+            target
+        } else {
+            params.release * self.gain + (1.0 - params.release) * target
+        };
+        self.gain
+    }
+}
+
+/// Tuning for [`NoiseGate`]: levels as linear amplitudes, the hold in frames
+/// and the ramps as [`smoothing_coefficient`]s.
+pub struct NoiseGateParams {
+    pub open_threshold: f32,
+    /// Lower than `open_threshold`, so a level hovering around the threshold
+    /// doesn't make the gate chatter.
+    pub close_threshold: f32,
+    pub hold_frames: u64,
+    pub attack: f32,
+    pub release: f32,
+}
+
+/// Noise gate that mutes the input between words. It opens as soon as a frame
+/// reaches the open threshold and ramps up within the attack time, so word
+/// onsets survive; it closes only after the hold time below the close
+/// threshold and then fades out over the release time instead of cutting off.
+#[derive(Default)]
+pub struct NoiseGate {
+    open: bool,
+    hold_remaining: u64,
+    gain: f32,
+}
+
+impl NoiseGate {
+    pub fn is_open(&self) -> bool {
+        self.open
+    }
+
+    /// Takes the peak of one frame and returns the gain to apply to it.
+    pub fn process(&mut self, peak: f32, params: &NoiseGateParams) -> f32 {
+        if peak >= params.open_threshold {
+            self.open = true;
+            self.hold_remaining = params.hold_frames;
+        } else if self.open {
+            if peak >= params.close_threshold {
+                self.hold_remaining = params.hold_frames;
+            } else if self.hold_remaining > 0 {
+                self.hold_remaining -= 1;
+            } else {
+                self.open = false;
+            }
+        }
+        let (target, coefficient) = if self.open {
+            (1.0, params.attack)
+        } else {
+            (0.0, params.release)
+        };
+        self.gain = coefficient * self.gain + (1.0 - coefficient) * target;
+        self.gain
+    }
+}
+
+/// Time constants for [`LevelBallistics`], in milliseconds. Zero makes a
+/// movement instant. The defaults follow a VU meter for RMS (300 ms both
+/// ways) and a PPM for peaks (fast rise, a fall of about 20 dB per 2.3 s).
+#[derive(serde::Serialize, serde::Deserialize, Clone, Copy)]
+pub struct MeterBallistics {
+    pub rms_attack_ms: f32,
+    pub rms_release_ms: f32,
+    pub peak_attack_ms: f32,
+    pub peak_release_ms: f32,
+    /// How long the held peak stays put before it falls back.
+    pub peak_hold_ms: f32,
+}
+
+impl Default for MeterBallistics {
+    fn default() -> Self {
+        Self {
+            rms_attack_ms: 300.0,
+            rms_release_ms: 300.0,
+            peak_attack_ms: 10.0,
+            peak_release_ms: 1_000.0,
+            peak_hold_ms: 1_500.0,
+        }
+    }
+}
+
+/// Peak levels, in dBFS, at which the meter's status changes from "silent"
+/// to "low", "good", "hot" and finally "clipping".
+#[derive(serde::Serialize, serde::Deserialize, Clone, Copy)]
+pub struct LevelThresholds {
+    pub low_dbfs: f32,
+    pub good_dbfs: f32,
+    pub hot_dbfs: f32,
+    pub clipping_dbfs: f32,
+}
+
+impl Default for LevelThresholds {
+    fn default() -> Self {
+        Self {
+            low_dbfs: -60.0,
+            good_dbfs: -30.0,
+            hot_dbfs: -6.0,
+            clipping_dbfs: -0.1,
+        }
+    }
+}
+
+impl LevelThresholds {
+    /// Thresholds must be finite, at most 0 dBFS and strictly rising.
+    pub fn validate(&self) -> Result<(), String> {
+        let levels = [
+            self.low_dbfs,
+            self.good_dbfs,
+            self.hot_dbfs,
+            self.clipping_dbfs,
+        ];
+        if levels
+            .iter()
+            .any(|level| !level.is_finite() || *level > 0.0)
+        {
+            return Err(format!(
+                "Level thresholds must be finite and at most 0 dBFS: {:?}",
+                levels
+            ));
+        }
+        if levels.windows(2).any(|pair| pair[0] >= pair[1]) {
+            return Err(format!(
+                "Level thresholds must rise from low to clipping: {:?}",
+                levels
+            ));
+        }
+        Ok(())
+    }
+
+    pub fn status(&self, peak_dbfs: f32) -> &'static str {
+        if peak_dbfs >= self.clipping_dbfs {
+            "clipping"
+        } else if peak_dbfs >= self.hot_dbfs {
+            "hot"
+        } else if peak_dbfs >= self.good_dbfs {
+            "good"
+        } else if peak_dbfs >= self.low_dbfs {
+            "low"
+        } else {
+            "silent"
+        }
+    }
+}
+
+/// Smoothed meter readings, carried from one level update to the next. Levels
+/// are linear amplitudes.
+#[derive(Default)]
+pub struct LevelBallistics {
+    pub rms: f32,
+    pub peak: f32,
+    /// Highest recent peak, held for `peak_hold_ms` and then released at the
+    /// peak release rate.
+    pub held_peak: f32,
+    hold_remaining_ms: f32,
+}
+
+impl LevelBallistics {
+    /// Moves the readings towards the levels measured over the last
+    /// `elapsed_ms`.
+    pub fn update(&mut self, rms: f32, peak: f32, elapsed_ms: f32, params: &MeterBallistics) {
+        let follow = |current: f32, target: f32, attack_ms: f32, release_ms: f32| {
+            let time_ms = if target > current {
+                attack_ms
+            } else {
+                release_ms
+            };
+            if time_ms <= 0.0 {
+                return target;
+            }
+            let coefficient = (-elapsed_ms / time_ms).exp();
+            coefficient * current + (1.0 - coefficient) * target
+        };
+        self.rms = follow(self.rms, rms, params.rms_attack_ms, params.rms_release_ms);
+        self.peak = follow(
+            self.peak,
+            peak,
+            params.peak_attack_ms,
+            params.peak_release_ms,
+        );
+        if peak >= self.held_peak {
+            self.held_peak = peak;
+            self.hold_remaining_ms = params.peak_hold_ms;
+        } else if self.hold_remaining_ms > 0.0 {
+            self.hold_remaining_ms -= elapsed_ms;
+        } else {
+            self.held_peak = follow(self.held_peak, self.peak, 0.0, params.peak_release_ms);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ulaw_matches_g711_code_points() {
+        assert_eq!(linear_to_ulaw(0), 0xFF);
+        assert_eq!(linear_to_ulaw(-1), 0x7F);
+        assert_eq!(linear_to_ulaw(i16::MAX), 0x80);
+        assert_eq!(linear_to_ulaw(i16::MIN), 0x00);
+    }
+
+    #[test]
+    fn ulaw_wav_header_sizes() {
+        let path = std::env::temp_dir().join(format!("ulaw_header_{}.wav", std::process::id()));
+        // An odd sample count, so the data chunk needs a pad byte.
+        encode_ulaw_wav(&path, &[0, i16::MAX, i16::MIN], ULAW_SAMPLE_RATE).unwrap();
+        let file = fs::read(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        let u16_at = |offset: usize| u16::from_le_bytes([file[offset], file[offset + 1]]);
+        let u32_at =
+            |offset: usize| u32::from_le_bytes(file[offset..offset + 4].try_into().unwrap());
+        assert_eq!(file.len(), 58 + 3 + 1);
+        assert_eq!(&file[0..4], b"RIFF");
+        assert_eq!(u32_at(4) as usize, file.len() - 8);
+        assert_eq!(&file[12..16], b"fmt ");
+        assert_eq!(u32_at(16), 18);
+        assert_eq!(u16_at(20), WAVE_FORMAT_MULAW);
+        assert_eq!(u32_at(24), ULAW_SAMPLE_RATE);
+        assert_eq!(&file[38..42], b"fact");
+        assert_eq!(u32_at(46), 3);
+        assert_eq!(&file[50..54], b"data");
+        assert_eq!(u32_at(54), 3);
+        assert_eq!(&file[58..61], &[0xFF, 0x80, 0x00]);
+    }
+}
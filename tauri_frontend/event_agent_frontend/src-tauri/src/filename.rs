@@ -0,0 +1,175 @@
+//! Recording file names built from the user's `filename_template`.
+
+/// Placeholders understood in a template. Dates and times are UTC.
+const PLACEHOLDERS: [&str; 5] = ["date", "time", "timestamp", "location", "country"];
+
+/// Values substituted into a template.
+pub struct TemplateValues<'a> {
+    /// Milliseconds since the Unix epoch.
+    pub timestamp: u64,
+    pub location: Option<&'a str>,
+    pub country: Option<&'a str>,
+}
+
+/// Expands `template` into a file stem. A trailing audio extension in the
+/// template is dropped, since the output format decides it. Characters that
+/// are not allowed in file names are replaced with `_`. Errors on an unknown
+/// placeholder, an unclosed brace, or a result with nothing left in it.
+pub fn expand_template(template: &str, values: &TemplateValues) -> Result<String, String> {
+    let template = strip_audio_extension(template.trim());
+    let mut expanded = String::new();
+    let mut rest = template;
+    while let Some(open) = rest.find('{') {
+        expanded.push_str(&rest[..open]);
+        let after = &rest[open + 1..];
+        let close = after
+            .find('}')
+            .ok_or_else(|| format!("Unclosed placeholder in {:?}", template))?;
+        let name = &after[..close];
+        expanded.push_str(&placeholder_value(name, values)?);
+        rest = &after[close + 1..];
+    }
+    expanded.push_str(rest);
+
+    let stem = sanitize(&expanded);
+    if stem.is_empty() {
+        return Err(format!(
+            "Template {:?} produces an empty file name",
+            template
+        ));
+    }
+    Ok(stem)
+}
+
+/// Turns a user-supplied name into a file stem the way templates are: a
+/// trailing audio extension is dropped and unsafe characters replaced. Path
+/// separators become `_`, so the result always names a file in place.
+pub fn sanitize_name(name: &str) -> Result<String, String> {
+    let stem = sanitize(strip_audio_extension(name.trim()));
+    if stem.is_empty() {
+        return Err(format!("{:?} is not a usable file name", name));
+    }
+    Ok(stem)
+}
+
+fn placeholder_value(name: &str, values: &TemplateValues) -> Result<String, String> {
+    let (year, month, day, hour, minute, second) = utc_from_millis(values.timestamp);
+    Ok(match name {
+        "date" => format!("{year:04}-{month:02}-{day:02}"),
+        "time" => format!("{hour:02}-{minute:02}-{second:02}"),
+        "timestamp" => values.timestamp.to_string(),
+        "location" => values.location.unwrap_or("unknown").to_string(),
+        "country" => values.country.unwrap_or("unknown").to_string(),
+        _ => {
+            return Err(format!(
+                "Unknown placeholder {{{}}}; expected one of {:?}",
+                name, PLACEHOLDERS
+            ))
+        }
+    })
+}
+
+fn strip_audio_extension(template: &str) -> &str {
+    for extension in [".wav", ".flac", ".opus", ".ogg"] {
+        let split = template.len().saturating_sub(extension.len());
+        if template.is_char_boundary(split) && template[split..].eq_ignore_ascii_case(extension) {
+            return &template[..split];
+        }
+    }
+    template
+}
+
+/// Replaces path separators, characters Windows rejects, and control
+/// characters, then trims the dots and spaces Windows strips silently.
+fn sanitize(name: &str) -> String {
+    let replaced: String = name
+        .chars()
+        .map(|c| {
+            if c.is_control() || matches!(c, '<' | '>' | ':' | '"' | '/' | '\\' | '|' | '?' | '*') {
+                '_'
+            } else {
+                c
+            }
+        })
+        .collect();
+    replaced.trim_matches(|c| c == '.' || c == ' ').to_string()
+}
+
+/// Splits a Unix timestamp into UTC calendar fields, using the days-to-civil
+/// conversion from Howard Hinnant's date algorithms.
+pub fn utc_from_millis(millis: u64) -> (i64, u32, u32, u32, u32, u32) {
+    let secs = millis / 1000;
+    let days = (secs / 86_400) as i64;
+    let secs_of_day = secs % 86_400;
+    let (hour, minute, second) = (
+        (secs_of_day / 3600) as u32,
+        (secs_of_day / 60 % 60) as u32,
+        (secs_of_day % 60) as u32,
+    );
+
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    (year, month, day, hour, minute, second)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn values() -> TemplateValues<'static> {
+        TemplateValues {
+            // 2024-02-29 12:34:56 UTC.
+            timestamp: 1_709_210_096_000,
+            location: Some("Main Hall"),
+            country: None,
+        }
+    }
+
+    #[test]
+    fn expands_placeholders() {
+        assert_eq!(
+            expand_template("rec_{date}_{time}_{location}_{country}", &values()).unwrap(),
+            "rec_2024-02-29_12-34-56_Main Hall_unknown"
+        );
+    }
+
+    #[test]
+    fn rejects_unknown_and_unclosed_placeholders() {
+        let unknown = expand_template("rec_{venue}", &values()).unwrap_err();
+        assert!(unknown.contains("Unknown placeholder {venue}"), "{unknown}");
+        let unclosed = expand_template("rec_{date", &values()).unwrap_err();
+        assert!(unclosed.contains("Unclosed placeholder"), "{unclosed}");
+    }
+
+    #[test]
+    fn drops_audio_extension_and_illegal_characters() {
+        assert_eq!(
+            expand_template("{timestamp}.WAV", &values()).unwrap(),
+            "1709210096000"
+        );
+        assert_eq!(
+            expand_template(" a/b:c*d?.wav ", &values()).unwrap(),
+            "a_b_c_d_"
+        );
+        assert!(expand_template("..wav", &values()).is_err());
+    }
+
+    #[test]
+    fn converts_epoch_millis_to_utc() {
+        assert_eq!(utc_from_millis(0), (1970, 1, 1, 0, 0, 0));
+        assert_eq!(
+            utc_from_millis(1_709_210_096_000),
+            (2024, 2, 29, 12, 34, 56)
+        );
+        assert_eq!(utc_from_millis(946_684_799_999), (1999, 12, 31, 23, 59, 59));
+        assert_eq!(utc_from_millis(1_704_067_200_000), (2024, 1, 1, 0, 0, 0));
+    }
+}
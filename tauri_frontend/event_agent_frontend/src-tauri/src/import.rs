@@ -0,0 +1,243 @@
+//! Converting outside audio files into recordings on a background thread, so
+//! long files don't hold up the UI.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use tauri::{AppHandle, Emitter, State};
+use tracing::{error, info};
+
+use crate::recordings::resolve_new_recording_path;
+use crate::{
+    audio, check_opus_options, create_wav_writer, duration_ms, recording_loudness, to_i16,
+    unix_millis, wav_spec, write_audio_file, write_sidecar, OutputFormat, RecordingMetadata,
+};
+
+/// Samples read or written between progress updates and cancellation checks.
+const IMPORT_CHUNK_SAMPLES: usize = 1 << 16;
+
+const IMPORT_CANCELLED: &str = "Import cancelled";
+
+/// Cancellation flag of the running import. Set by `cancel_import` and
+/// cleared when a new one starts.
+#[derive(Clone, Default)]
+pub struct ImportState {
+    cancel: Arc<AtomicBool>,
+}
+
+#[derive(serde::Deserialize, Clone, Copy, Default)]
+#[serde(default)]
+pub struct ImportOptions {
+    /// Resample to this rate; the input's rate is kept otherwise.
+    sample_rate: Option<u32>,
+    /// Downmix to a single channel.
+    mono: bool,
+    /// Scale the audio so its peak lands on this level.
+    normalize_peak_dbfs: Option<f32>,
+}
+
+#[derive(serde::Serialize, Clone)]
+struct ImportProgress {
+    input: String,
+    /// 0 to 100.
+    percent: u8,
+}
+
+#[derive(serde::Serialize, Clone)]
+struct ImportComplete {
+    input: String,
+    output: String,
+    metadata: RecordingMetadata,
+}
+
+#[derive(serde::Serialize, Clone)]
+struct ImportError {
+    input: String,
+    message: String,
+}
+
+/// Converts the WAV file `input` into a 16-bit recording at `output`, a new
+/// file in the recordings directory whose extension picks the format, and
+/// returns the output path. The work happens on a background thread that
+/// reports `import-progress` as it goes and ends with `import-complete` or
+/// `import-error`; errors found before it starts are returned directly.
+#[tauri::command]
+pub fn import_audio(
+    app: AppHandle,
+    state: State<ImportState>,
+    input: String,
+    output: String,
+    options: Option<ImportOptions>,
+) -> Result<String, String> {
+    let options = options.unwrap_or_default();
+    if options.sample_rate == Some(0) {
+        return Err("Sample rate must be greater than zero".to_string());
+    }
+    if let Some(target) = options.normalize_peak_dbfs {
+        if !target.is_finite() || target > 0.0 {
+            return Err(format!("Invalid normalization target: {} dBFS", target));
+        }
+    }
+    let input_path = PathBuf::from(&input);
+    if !input_path.is_file() {
+        return Err(format!("{} is not a file", input));
+    }
+    let output_path = resolve_new_recording_path(&app, &output)?;
+    let format = output_path
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .and_then(OutputFormat::from_extension)
+        .ok_or_else(|| format!("{} is not a supported audio file name", output))?;
+    check_opus_options(format, None)?;
+
+    let cancel = state.cancel.clone();
+    cancel.store(false, Ordering::SeqCst);
+    let output = output_path.to_string_lossy().to_string();
+    std::thread::spawn(move || {
+        let result = run_import(&app, &input, &output_path, format, options, &cancel);
+        match result {
+            Ok(metadata) => {
+                info!("Imported {} as {}", input, output_path.display());
+                let complete = ImportComplete {
+                    input,
+                    output: output_path.to_string_lossy().to_string(),
+                    metadata,
+                };
+                if let Err(e) = app.emit("import-complete", complete) {
+                    error!("failed to emit import-complete: {:?}", e);
+                }
+            }
+            Err(message) => {
+                error!("import of {} failed: {}", input, message);
+                // Don't leave a partial file behind.
+                let _ = fs::remove_file(&output_path);
+                if let Err(e) = app.emit("import-error", ImportError { input, message }) {
+                    error!("failed to emit import-error: {:?}", e);
+                }
+            }
+        }
+    });
+    Ok(output)
+}
+
+/// Asks the running import to stop; it then reports `import-error` and removes
+/// its partial output. Does nothing when none is running.
+#[tauri::command]
+pub fn cancel_import(state: State<ImportState>) {
+    state.cancel.store(true, Ordering::SeqCst);
+}
+
+fn run_import(
+    app: &AppHandle,
+    input: &str,
+    output: &Path,
+    format: OutputFormat,
+    options: ImportOptions,
+    cancel: &AtomicBool,
+) -> Result<RecordingMetadata, String> {
+    let mut last_percent = None;
+    let mut progress = |done: usize, total: usize, from: u8, to: u8| -> Result<(), String> {
+        if cancel.load(Ordering::SeqCst) {
+            return Err(IMPORT_CANCELLED.to_string());
+        }
+        let span = (to - from) as usize;
+        let percent = from + (done.min(total) * span / total.max(1)) as u8;
+        if last_percent != Some(percent) {
+            last_percent = Some(percent);
+            let payload = ImportProgress {
+                input: input.to_string(),
+                percent,
+            };
+            let _ = app.emit("import-progress", payload);
+        }
+        Ok(())
+    };
+
+    // Reading takes the first half of the progress, writing the second.
+    let mut reader =
+        hound::WavReader::open(input).map_err(|e| format!("Failed to open {}: {}", input, e))?;
+    let spec = reader.spec();
+    let total = reader.len() as usize;
+    let read_error = |e: hound::Error| format!("Failed to read {}: {}", input, e);
+    let mut samples = Vec::with_capacity(total);
+    match spec.sample_format {
+        hound::SampleFormat::Int => {
+            let scale = (1i64 << (spec.bits_per_sample.max(1) - 1)) as f32;
+            for sample in reader.samples::<i32>() {
+                samples.push(sample.map_err(read_error)? as f32 / scale);
+                if samples.len() % IMPORT_CHUNK_SAMPLES == 0 {
+                    progress(samples.len(), total, 0, 50)?;
+                }
+            }
+        }
+        hound::SampleFormat::Float => {
+            for sample in reader.samples::<f32>() {
+                samples.push(sample.map_err(read_error)?);
+                if samples.len() % IMPORT_CHUNK_SAMPLES == 0 {
+                    progress(samples.len(), total, 0, 50)?;
+                }
+            }
+        }
+    }
+    progress(total, total, 0, 50)?;
+
+    let mut channels = spec.channels.max(1);
+    if options.mono && channels > 1 {
+        samples = audio::downmix_to_mono(&samples, channels);
+        channels = 1;
+    }
+    let mut samples: Vec<i16> = samples.into_iter().map(to_i16).collect();
+    let mut sample_rate = spec.sample_rate;
+    if let Some(target_rate) = options.sample_rate {
+        if target_rate != sample_rate {
+            samples = audio::resample_interleaved(&samples, channels, sample_rate, target_rate);
+            sample_rate = target_rate;
+        }
+    }
+    let normalization_gain = options
+        .normalize_peak_dbfs
+        .and_then(|target| audio::normalize_peak(&mut samples, target));
+    progress(0, 1, 50, 100)?;
+
+    if format == OutputFormat::Wav {
+        let mut writer = create_wav_writer(output, wav_spec(sample_rate, channels))?;
+        for (index, chunk) in samples.chunks(IMPORT_CHUNK_SAMPLES).enumerate() {
+            for &sample in chunk {
+                writer.write_sample(sample).map_err(|e| e.to_string())?;
+            }
+            progress((index + 1) * IMPORT_CHUNK_SAMPLES, samples.len(), 50, 100)?;
+        }
+        writer.finalize().map_err(|e| e.to_string())?;
+    } else {
+        write_audio_file(output, &samples, sample_rate, channels, format)?;
+    }
+    progress(1, 1, 50, 100)?;
+
+    let peak = samples
+        .iter()
+        .map(|&sample| (sample as i32).unsigned_abs())
+        .max()
+        .unwrap_or(0);
+    let metadata = RecordingMetadata {
+        sample_rate,
+        channels,
+        duration_ms: duration_ms(samples.len(), sample_rate, channels),
+        device_name: None,
+        created_at: unix_millis()?,
+        peak_dbfs: audio::linear_to_dbfs((peak as f32 / i16::MAX as f32).min(1.0)),
+        integrated_lufs: recording_loudness(&samples, channels, sample_rate, false),
+        format,
+        normalization_gain,
+        denoised: false,
+        bits_per_sample: 16,
+        mid_side: false,
+        tags: Vec::new(),
+        note: None,
+        location: None,
+        country: None,
+        keep: false,
+    };
+    write_sidecar(output, &metadata)?;
+    Ok(metadata)
+}
@@ -1,11 +1,33 @@
 // Learn more about Tauri commands at https://tauri.app/develop/calling-rust/
+mod audio;
+mod filename;
+mod import;
+mod paste;
+mod permission;
+mod playback;
+mod recordings;
+mod recovery;
+mod transcribe;
+mod tray;
+mod upload;
+mod wav_info;
+
+use base64::Engine;
 use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
 use cpal::SampleFormat;
-use std::fs;
-use std::path::PathBuf;
-use std::sync::{Arc, Mutex};
-use std::time::{SystemTime, UNIX_EPOCH};
-use tauri::{AppHandle, Emitter, Manager, State, Window};
+use std::collections::{BTreeMap, VecDeque};
+use std::fs::{self, File};
+use std::io::{BufWriter, Cursor};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tauri::{AppHandle, Emitter, Manager, RunEvent, State};
+use tauri_plugin_global_shortcut::{GlobalShortcutExt, Shortcut};
+use tracing::{error, info, warn};
+
+const DEFAULT_SHORTCUT: &str = "Alt+E";
+const RECORDING_FILE_PREFIX: &str = "event_searcher_recording_";
 
 #[allow(dead_code)]
 struct CpalStreamWrapper(cpal::Stream);
@@ -13,317 +35,4695 @@ struct CpalStreamWrapper(cpal::Stream);
 unsafe impl Send for CpalStreamWrapper {}
 unsafe impl Sync for CpalStreamWrapper {}
 
+type FileWavWriter = hound::WavWriter<BufWriter<File>>;
+type StreamGuard<'a> = MutexGuard<'a, Option<CpalStreamWrapper>>;
+
+/// Locking that survives a panic in another holder of the lock.
+///
+/// A panic in the audio callback while it holds part of `RecordingState`
+/// would otherwise poison that mutex and make every later command fail until
+/// the app restarts. Each value behind these locks is valid on its own (a
+/// buffer, a setting, a handle), so carrying on with the inner guard is safe.
+trait MutexExt<T> {
+    fn lock_or_recover(&self) -> MutexGuard<'_, T>;
+}
+
+impl<T> MutexExt<T> for Mutex<T> {
+    fn lock_or_recover(&self) -> MutexGuard<'_, T> {
+        self.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+#[derive(Clone)]
 struct RecordingState {
     stream: Arc<Mutex<Option<CpalStreamWrapper>>>,
     samples: Arc<Mutex<Vec<i16>>>,
     sample_rate: Arc<Mutex<u32>>,
+    // Interleaved channel count of `samples`, as written to the WAV header.
+    output_channels: Arc<Mutex<u16>>,
+    // Set when the recording is streamed straight to disk instead of `samples`.
+    writer: Arc<Mutex<Option<FileWavWriter>>>,
+    output_path: Arc<Mutex<Option<PathBuf>>>,
+    // Caller-chosen destination, used instead of a timestamped file name.
+    requested_path: Arc<Mutex<Option<PathBuf>>>,
+    // Segmented recordings: the path segment names derive from, the finished
+    // segments so far, and how many samples the current segment holds.
+    segment_base: Arc<Mutex<Option<PathBuf>>>,
+    segments: Arc<Mutex<Vec<PathBuf>>>,
+    segment_written: Arc<AtomicU64>,
+    paused: Arc<AtomicBool>,
+    // Set by `set_mute`: captured audio is replaced with silence.
+    muted: Arc<AtomicBool>,
+    started_at: Arc<Mutex<Option<Instant>>>,
+    // End of the warm-up of `start_recording_with_delay`. Until then the
+    // stream runs but its frames are dropped.
+    warmup_until: Arc<Mutex<Option<Instant>>>,
+    captured_samples: Arc<AtomicU64>,
+    // Sequence number of the next `pcm-chunk` event.
+    pcm_sequence: Arc<AtomicU64>,
+    auto_stopping: Arc<AtomicBool>,
+    // Loudest normalized sample seen so far, for the sidecar's peak_dbfs.
+    peak: Arc<Mutex<f32>>,
+    device_name: Arc<Mutex<Option<String>>>,
+    // Linear input gain applied before samples are clamped and stored.
+    gain: Arc<Mutex<f32>>,
+    silence: Arc<Mutex<SilenceDetection>>,
+    // Consecutive frames whose peak stayed under the silence threshold.
+    silent_frames: Arc<AtomicU64>,
+    silence_reported: Arc<AtomicBool>,
+    // Partially filled waveform bucket carried over between callbacks.
+    waveform_bucket: Arc<Mutex<WaveformBucket>>,
+    // High-pass cutoff in Hz; 0 disables the filter.
+    highpass_cutoff: Arc<Mutex<f32>>,
+    // Filter history, kept across callbacks so buffers join without clicks.
+    highpass: Arc<Mutex<audio::HighPassFilter>>,
+    // Voice-activity gate: whether speech is in progress, and how many quiet
+    // frames have passed since the input last crossed the onset threshold.
+    speaking: Arc<AtomicBool>,
+    vad_quiet_frames: Arc<AtomicU64>,
+    agc: Arc<Mutex<AgcSettings>>,
+    // AGC level estimate and gain, carried over between callbacks.
+    agc_state: Arc<Mutex<audio::AutomaticGain>>,
+    // Unquantized audio for recordings written above 16 bits: a copy of
+    // `samples` at 24 bits, and the only copy of a 32-bit float recording,
+    // unclamped so levels above 0 dBFS survive.
+    hires_samples: Arc<Mutex<Vec<f32>>>,
+    bit_depth: Arc<Mutex<u16>>,
+    noise_gate: Arc<Mutex<NoiseGateSettings>>,
+    // Gate position and gain, carried over between callbacks.
+    noise_gate_state: Arc<Mutex<audio::NoiseGate>>,
+    limiter: Arc<Mutex<LimiterSettings>>,
+    // Limiter gain, carried over between callbacks so the release is smooth.
+    limiter_state: Arc<Mutex<audio::Limiter>>,
+    // Mixed capture: the system-audio stream and what it captured, kept at
+    // the loopback device's rate until they are mixed into `samples` on stop.
+    // A rate of 0 means no loopback stream belongs to this recording.
+    loopback_stream: Arc<Mutex<Option<CpalStreamWrapper>>>,
+    loopback_samples: Arc<Mutex<Vec<i16>>>,
+    loopback_rate: Arc<Mutex<u32>>,
+    // Minimum time between `audio-level` events, and the levels gathered from
+    // the buffers since the last one.
+    level_interval: Arc<Mutex<Duration>>,
+    level_meter: Arc<Mutex<LevelMeter>>,
+    // Meter time constants, and the smoothed levels carried between events.
+    meter_ballistics: Arc<Mutex<audio::MeterBallistics>>,
+    level_thresholds: Arc<Mutex<audio::LevelThresholds>>,
+    level_ballistics: Arc<Mutex<audio::LevelBallistics>>,
+    clip_meter: Arc<Mutex<ClipMeter>>,
+    // Audio of the last finished recording as written, for
+    // `get_last_samples`. `None` if it was streamed to disk unchanged.
+    last_samples: Arc<Mutex<Option<CachedSamples>>>,
+    // Crash-recovery copy of an in-memory recording, and a counter that tells
+    // the checkpoint thread of an earlier recording to exit.
+    recovery: Arc<Mutex<Option<recovery::RecoveryFile>>>,
+    checkpoint_generation: Arc<AtomicU64>,
+    // Audio from the monitor stream that precedes the next recording; see
+    // `RecordingConfig::pre_roll_ms`.
+    pre_roll: Arc<Mutex<PreRoll>>,
+    // The last cancelled recording, kept for `undo_cancel`.
+    cancelled: Arc<Mutex<Option<CancelledRecording>>>,
+    // Negotiated stream parameters and callback timing, for `get_stream_info`.
+    stream_info: Arc<Mutex<StreamInfo>>,
+    // Level-only stream from `start_monitoring`; never set while recording.
+    monitor_stream: Arc<Mutex<Option<CpalStreamWrapper>>>,
+    // Set by `set_pre_roll_armed`. Pre-roll only holds the microphone open
+    // while armed, and never at startup.
+    pre_roll_armed: Arc<AtomicBool>,
+    // Set while `mic_test` has the microphone; recording and monitoring wait.
+    mic_testing: Arc<AtomicBool>,
+}
+
+/// What the input stream actually runs with, as reported by
+/// `get_stream_info`.
+#[derive(serde::Serialize, Clone, Default)]
+struct StreamInfo {
+    sample_rate: u32,
+    channels: u16,
+    sample_format: String,
+    /// Frames per callback: the fixed size if one was requested, otherwise
+    /// the size of the last buffer the driver delivered. cpal does not expose
+    /// the negotiated size directly.
+    buffer_frames: Option<u32>,
+    /// Latency that one buffer adds, from `buffer_frames`.
+    buffer_latency_ms: Option<f32>,
+    /// Smoothed time between consecutive input callbacks.
+    callback_interval_ms: Option<f32>,
+    #[serde(skip)]
+    fixed_buffer: bool,
+    #[serde(skip)]
+    last_callback: Option<Instant>,
+}
+
+/// Weight of the newest callback in `StreamInfo::callback_interval_ms`.
+const CALLBACK_INTERVAL_SMOOTHING: f32 = 0.1;
+
+impl StreamInfo {
+    fn record_callback(&mut self, frames: usize) {
+        let now = Instant::now();
+        if let Some(last) = self.last_callback {
+            let interval = now.duration_since(last).as_secs_f32() * 1000.0;
+            self.callback_interval_ms = Some(match self.callback_interval_ms {
+                Some(mean) => mean + CALLBACK_INTERVAL_SMOOTHING * (interval - mean),
+                None => interval,
+            });
+        }
+        self.last_callback = Some(now);
+        if !self.fixed_buffer {
+            self.buffer_frames = Some(frames as u32);
+        }
+        self.buffer_latency_ms = self
+            .buffer_frames
+            .map(|frames| frames as f32 * 1000.0 / self.sample_rate.max(1) as f32);
+    }
+}
+
+struct CachedSamples {
+    samples: Vec<i16>,
+    channels: u16,
+}
+
+/// Default spacing of `audio-level` events, about 30 per second.
+const DEFAULT_LEVEL_INTERVAL_MS: u64 = 33;
+
+/// Levels accumulated between two `audio-level` events.
+#[derive(Default)]
+struct LevelMeter {
+    last_emit: Option<Instant>,
+    sum_squares: f32,
+    samples: usize,
+    peak: f32,
+    limiter_reduction_db: f32,
+}
+
+/// Clipped samples counted between two `clipping-detected` events.
+#[derive(Default)]
+struct ClipMeter {
+    last_emit: Option<Instant>,
+    clipped: u64,
+}
+
+/// Most recent monitor audio, in the layout the monitor was opened with.
+#[derive(Default)]
+struct PreRoll {
+    samples: VecDeque<i16>,
+    device_name: String,
+    sample_rate: u32,
+    channels: u16,
+    channel_select: Option<usize>,
+}
+
+/// Upper bound for `RecordingConfig::pre_roll_ms`.
+const MAX_PRE_ROLL_MS: u32 = 5_000;
+
+/// Minimum time between two `clipping-detected` events.
+const CLIP_EVENT_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Automatic gain control settings. The time constants and limits are fixed;
+/// only the on/off switch and the target level are user-facing.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Copy)]
+struct AgcSettings {
+    enabled: bool,
+    target_dbfs: f32,
+}
+
+impl Default for AgcSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            target_dbfs: -18.0,
+        }
+    }
+}
+
+const AGC_ATTACK_MS: f32 = 10.0;
+const AGC_RELEASE_MS: f32 = 500.0;
+/// Input quieter than this is treated as background noise and never boosted.
+const AGC_NOISE_FLOOR_DBFS: f32 = -50.0;
+const AGC_MAX_GAIN: f32 = 16.0;
+
+/// Noise gate applied after gain and AGC. Off while `threshold_dbfs` is unset.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Copy)]
+struct NoiseGateSettings {
+    threshold_dbfs: Option<f32>,
+    hold_ms: u64,
+}
+
+impl Default for NoiseGateSettings {
+    fn default() -> Self {
+        Self {
+            threshold_dbfs: None,
+            hold_ms: 200,
+        }
+    }
+}
+
+const GATE_ATTACK_MS: f32 = 1.0;
+const GATE_RELEASE_MS: f32 = 50.0;
+/// How far below the open threshold the level must fall to start closing.
+const GATE_HYSTERESIS_DB: f32 = 6.0;
+
+/// Soft limiter applied after gain and AGC, ahead of the final clamp.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Copy)]
+struct LimiterSettings {
+    enabled: bool,
+    threshold_dbfs: f32,
+    release_ms: f32,
+}
+
+impl Default for LimiterSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            threshold_dbfs: -1.0,
+            release_ms: 150.0,
+        }
+    }
+}
+
+impl RecordingState {
+    fn new() -> Self {
+        Self {
+            stream: Arc::new(Mutex::new(None)),
+            samples: Arc::new(Mutex::new(Vec::new())),
+            sample_rate: Arc::new(Mutex::new(0)),
+            output_channels: Arc::new(Mutex::new(1)),
+            writer: Arc::new(Mutex::new(None)),
+            output_path: Arc::new(Mutex::new(None)),
+            requested_path: Arc::new(Mutex::new(None)),
+            segment_base: Arc::new(Mutex::new(None)),
+            segments: Arc::new(Mutex::new(Vec::new())),
+            segment_written: Arc::new(AtomicU64::new(0)),
+            paused: Arc::new(AtomicBool::new(false)),
+            muted: Arc::new(AtomicBool::new(false)),
+            started_at: Arc::new(Mutex::new(None)),
+            warmup_until: Arc::new(Mutex::new(None)),
+            captured_samples: Arc::new(AtomicU64::new(0)),
+            pcm_sequence: Arc::new(AtomicU64::new(0)),
+            auto_stopping: Arc::new(AtomicBool::new(false)),
+            peak: Arc::new(Mutex::new(0.0)),
+            device_name: Arc::new(Mutex::new(None)),
+            gain: Arc::new(Mutex::new(1.0)),
+            silence: Arc::new(Mutex::new(SilenceDetection::default())),
+            silent_frames: Arc::new(AtomicU64::new(0)),
+            silence_reported: Arc::new(AtomicBool::new(false)),
+            waveform_bucket: Arc::new(Mutex::new(WaveformBucket::default())),
+            highpass_cutoff: Arc::new(Mutex::new(DEFAULT_HIGHPASS_CUTOFF_HZ)),
+            highpass: Arc::new(Mutex::new(audio::HighPassFilter::default())),
+            speaking: Arc::new(AtomicBool::new(false)),
+            vad_quiet_frames: Arc::new(AtomicU64::new(0)),
+            agc: Arc::new(Mutex::new(AgcSettings::default())),
+            agc_state: Arc::new(Mutex::new(audio::AutomaticGain::default())),
+            hires_samples: Arc::new(Mutex::new(Vec::new())),
+            bit_depth: Arc::new(Mutex::new(16)),
+            noise_gate: Arc::new(Mutex::new(NoiseGateSettings::default())),
+            noise_gate_state: Arc::new(Mutex::new(audio::NoiseGate::default())),
+            limiter: Arc::new(Mutex::new(LimiterSettings::default())),
+            limiter_state: Arc::new(Mutex::new(audio::Limiter::default())),
+            loopback_stream: Arc::new(Mutex::new(None)),
+            loopback_samples: Arc::new(Mutex::new(Vec::new())),
+            loopback_rate: Arc::new(Mutex::new(0)),
+            level_interval: Arc::new(Mutex::new(Duration::from_millis(DEFAULT_LEVEL_INTERVAL_MS))),
+            level_meter: Arc::new(Mutex::new(LevelMeter::default())),
+            meter_ballistics: Arc::new(Mutex::new(audio::MeterBallistics::default())),
+            level_thresholds: Arc::new(Mutex::new(audio::LevelThresholds::default())),
+            level_ballistics: Arc::new(Mutex::new(audio::LevelBallistics::default())),
+            clip_meter: Arc::new(Mutex::new(ClipMeter::default())),
+            last_samples: Arc::new(Mutex::new(None)),
+            recovery: Arc::new(Mutex::new(None)),
+            checkpoint_generation: Arc::new(AtomicU64::new(0)),
+            pre_roll: Arc::new(Mutex::new(PreRoll::default())),
+            cancelled: Arc::new(Mutex::new(None)),
+            stream_info: Arc::new(Mutex::new(StreamInfo::default())),
+            monitor_stream: Arc::new(Mutex::new(None)),
+            pre_roll_armed: Arc::new(AtomicBool::new(false)),
+            mic_testing: Arc::new(AtomicBool::new(false)),
+        }
+    }
+}
+
+/// Default high-pass cutoff, low enough to leave speech untouched.
+const DEFAULT_HIGHPASS_CUTOFF_HZ: f32 = 80.0;
+
+/// Length of one `waveform-frame` bucket.
+const WAVEFORM_BUCKET_MS: u32 = 10;
+
+#[derive(Default)]
+struct WaveformBucket {
+    peak: f32,
+    frames: usize,
+}
+
+/// Thresholds for the dead-mic warning.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Copy)]
+struct SilenceDetection {
+    threshold_dbfs: f32,
+    timeout_ms: u64,
+}
+
+impl Default for SilenceDetection {
+    fn default() -> Self {
+        Self {
+            threshold_dbfs: -60.0,
+            timeout_ms: 5_000,
+        }
+    }
+}
+
+#[derive(serde::Serialize, Clone)]
+struct SilenceDetected {
+    silent_ms: u64,
+}
+
+/// Voice-activity trigger: frames are only recorded from the moment the input
+/// reaches `onset_dbfs` until it has stayed below it for `hangover_ms`.
+#[derive(serde::Deserialize, Clone, Copy)]
+#[serde(default)]
+struct VadSettings {
+    onset_dbfs: f32,
+    hangover_ms: u64,
+}
+
+impl Default for VadSettings {
+    fn default() -> Self {
+        Self {
+            onset_dbfs: -40.0,
+            hangover_ms: 800,
+        }
+    }
+}
+
+/// `VadSettings` converted to the units `process_input` works in.
+#[derive(Clone, Copy)]
+struct VadGate {
+    onset: f32,
+    hangover_frames: u64,
+}
+
+/// Channel layout written to the recording.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+enum ChannelMode {
+    /// Mix every input channel into one; see `audio::downmix_weights`.
+    #[default]
+    Mono,
+    /// Keep the first two input channels interleaved as L/R. Mono inputs are
+    /// duplicated to both sides.
+    Stereo,
+}
+
+impl ChannelMode {
+    fn output_channels(self) -> u16 {
+        match self {
+            ChannelMode::Mono => 1,
+            ChannelMode::Stereo => 2,
+        }
+    }
+}
+
+/// What a mono recording is made of when no `channel_select` is given.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+enum DownmixMode {
+    /// Mix every input channel; see `audio::downmix_weights`.
+    #[default]
+    Average,
+    FirstChannel,
+    /// A zero-based input channel. Devices with fewer channels fall back to
+    /// `Average`.
+    SpecificChannel(usize),
+}
+
+/// Where `start_recording` takes its audio from.
+#[derive(serde::Deserialize, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+enum CaptureSource {
+    #[default]
+    Microphone,
+    /// What the system is playing, captured from an output device. Only
+    /// available where cpal supports loopback (WASAPI on Windows).
+    SystemLoopback,
+    /// The microphone and the default output device recorded together and
+    /// mixed into one file on stop. Recorded in memory only.
+    Mixed,
+}
+
+/// Sample formats a caller can ask the input device for, mirroring the ones
+/// `process_input` handles.
+#[derive(serde::Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+enum InputSampleFormat {
+    F32,
+    I16,
+    U16,
+}
+
+impl InputSampleFormat {
+    fn to_cpal(self) -> SampleFormat {
+        match self {
+            InputSampleFormat::F32 => SampleFormat::F32,
+            InputSampleFormat::I16 => SampleFormat::I16,
+            InputSampleFormat::U16 => SampleFormat::U16,
+        }
+    }
+}
+
+/// Caller-supplied options for a new recording.
+#[derive(Default)]
+struct StartOptions {
+    source: CaptureSource,
+    /// Input device, or the output device to loop back for `SystemLoopback`.
+    /// `Mixed` applies it to the microphone and loops back the default output.
+    device_name: Option<String>,
+    /// Microphone to record, from `DeviceInfo::id`. Tried before `device_name`.
+    device_id: Option<String>,
+    stream_to_disk: bool,
+    max_duration_secs: Option<u32>,
+    channel_mode: ChannelMode,
+    /// Where to save the recording instead of the recordings directory.
+    output_path: Option<PathBuf>,
+    /// Only record while speech is detected.
+    vad: Option<VadSettings>,
+    /// Split the recording into files of this length. Implies streaming to
+    /// disk.
+    segment_secs: Option<u32>,
+    /// Open the microphone in this format instead of the device default.
+    preferred_format: Option<InputSampleFormat>,
+    /// Open the microphone at this rate instead of the device default.
+    sample_rate: Option<u32>,
+    /// WAV bit depth, one of `SUPPORTED_BIT_DEPTHS`. Defaults to 16.
+    bit_depth: Option<u16>,
+    /// Record only this zero-based input channel instead of a downmix.
+    channel_select: Option<usize>,
+    /// Saved default for mono recordings without `channel_select`.
+    downmix_mode: DownmixMode,
+    /// Ask the driver for callbacks of exactly this many frames, so levels
+    /// update at a steady rate. Very small buffers can overrun and drop audio.
+    buffer_frames: Option<u32>,
+    /// Emit every recorded buffer as a `pcm-chunk` event.
+    pcm_events: bool,
+    /// Run the stream this long, dropping what it captures, before recording.
+    start_delay: Option<Duration>,
+}
+
+impl StartOptions {
+    /// The single input channel to record, if any: `channel_select`, or for
+    /// mono the channel picked by `downmix_mode`.
+    fn selected_channel(&self, input_channels: u16) -> Option<usize> {
+        if self.channel_select.is_some() || self.channel_mode != ChannelMode::Mono {
+            return self.channel_select;
+        }
+        match self.downmix_mode {
+            DownmixMode::Average => None,
+            DownmixMode::FirstChannel => Some(0),
+            DownmixMode::SpecificChannel(index) if index < input_channels as usize => Some(index),
+            DownmixMode::SpecificChannel(index) => {
+                warn!(
+                    "downmix channel {} is missing on a {}-channel device; averaging instead",
+                    index, input_channels
+                );
+                None
+            }
+        }
+    }
+}
+
+/// WAV sample sizes that can be written: 16- and 24-bit integer, and 32-bit
+/// float. Anything above 16 needs an f32 source to be worth keeping.
+const SUPPORTED_BIT_DEPTHS: [u16; 3] = [16, 24, 32];
+
+/// Container written by `stop_recording`.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+enum OutputFormat {
+    #[default]
+    Wav,
+    Flac,
+    /// Ogg Opus, lossy; see `audio::encode_opus`.
+    Opus,
+}
+
+/// Opus bitrate when none is given, in bits per second. Plenty for speech.
+const DEFAULT_OPUS_BITRATE: u32 = 24_000;
+
+/// Bitrates libopus accepts, in bits per second.
+const OPUS_BITRATE_RANGE: std::ops::RangeInclusive<u32> = 6_000..=510_000;
+
+impl OutputFormat {
+    fn extension(self) -> &'static str {
+        match self {
+            OutputFormat::Wav => "wav",
+            OutputFormat::Flac => "flac",
+            OutputFormat::Opus => "opus",
+        }
+    }
+
+    fn from_extension(extension: &str) -> Option<Self> {
+        match extension.to_ascii_lowercase().as_str() {
+            "wav" => Some(OutputFormat::Wav),
+            "flac" => Some(OutputFormat::Flac),
+            "opus" | "ogg" => Some(OutputFormat::Opus),
+            _ => None,
+        }
+    }
+}
+
+/// Caller-supplied options for finalizing a recording.
+#[derive(Default)]
+struct StopOptions {
+    format: OutputFormat,
+    /// Resample to this rate before writing; see `audio::resample_linear`.
+    target_sample_rate: Option<u32>,
+    /// Scale the recording so its peak lands on this level.
+    normalize_peak_dbfs: Option<f32>,
+    /// Save a recording that captured no samples instead of discarding it.
+    allow_empty: bool,
+    /// Run `audio::spectral_denoise` over the recording.
+    denoise: bool,
+    /// Store a stereo recording as mid/side; see `audio::stereo_to_mid_side`.
+    mid_side: bool,
+    /// Saved as the sidecar's note.
+    description: Option<String>,
+    /// Embed the date, description and saved location in a WAV file; see
+    /// `wav_info::append_info_chunk`.
+    embed_info: bool,
+    /// Bits per second for `OutputFormat::Opus`; `DEFAULT_OPUS_BITRATE` if
+    /// unset.
+    opus_bitrate: Option<u32>,
+}
+
+impl StopOptions {
+    /// Whether the captured audio has to be rewritten rather than kept as
+    /// streamed to disk.
+    fn rewrites_audio(&self, sample_rate: u32) -> bool {
+        self.format != OutputFormat::Wav
+            || self.normalize_peak_dbfs.is_some()
+            || self.denoise
+            || self.mid_side
+            || self
+                .target_sample_rate
+                .is_some_and(|target_rate| target_rate != sample_rate)
+    }
+}
+
+/// Named bundles of recording settings for callers that would rather not pick
+/// formats and bit depths. Arguments passed to `start_recording` and
+/// `stop_recording` override the preset, and so do the other fields of
+/// `RecordingConfig`.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "lowercase")]
+enum QualityPreset {
+    /// 16 kHz mono Opus, or FLAC in builds without the `opus` feature.
+    Voice,
+    /// Mono 16-bit WAV at the device rate.
+    Standard,
+    /// 48 kHz stereo 24-bit WAV. Needs a microphone with float samples.
+    HiFi,
+}
+
+impl QualityPreset {
+    fn start_options(self) -> StartOptions {
+        match self {
+            QualityPreset::Voice | QualityPreset::Standard => StartOptions::default(),
+            QualityPreset::HiFi => StartOptions {
+                channel_mode: ChannelMode::Stereo,
+                sample_rate: Some(48_000),
+                bit_depth: Some(24),
+                ..StartOptions::default()
+            },
+        }
+    }
+
+    /// Resolved for every save by `saved_stop_options`.
+    fn stop_options(self) -> StopOptions {
+        match self {
+            QualityPreset::Voice => StopOptions {
+                format: if cfg!(feature = "opus") {
+                    OutputFormat::Opus
+                } else {
+                    OutputFormat::Flac
+                },
+                target_sample_rate: Some(16_000),
+                ..StopOptions::default()
+            },
+            QualityPreset::Standard | QualityPreset::HiFi => StopOptions::default(),
+        }
+    }
+}
+
+/// Per-stream parameters fixed when `start_recording` builds the stream.
+#[derive(Clone, Copy)]
+struct CaptureSettings {
+    channels: usize,
+    sample_rate: u32,
+    channel_mode: ChannelMode,
+    max_samples: Option<u64>,
+    waveform_bucket_frames: usize,
+    vad: Option<VadGate>,
+    /// Interleaved samples per segment file.
+    segment_samples: Option<u64>,
+    /// Bit depth the recording is written at. Above 16, full-resolution
+    /// samples are kept next to the 16-bit ones.
+    bit_depth: u16,
+    /// Meter only; nothing is stored. Used by `start_monitoring`.
+    monitor_only: bool,
+    /// The one input channel kept from each frame; the others are dropped.
+    channel_select: Option<usize>,
+    /// Interleaved samples a monitor stream keeps in `RecordingState::pre_roll`.
+    pre_roll_samples: usize,
+    /// Emit recorded samples as `pcm-chunk` events.
+    pcm_events: bool,
+}
+
+impl CaptureSettings {
+    /// Settings for a plain stream with every optional feature off.
+    fn for_stream(config: &cpal::StreamConfig, channel_mode: ChannelMode) -> Self {
+        Self {
+            channels: config.channels as usize,
+            sample_rate: config.sample_rate.0,
+            channel_mode,
+            max_samples: None,
+            waveform_bucket_frames: (config.sample_rate.0 * WAVEFORM_BUCKET_MS / 1000).max(1)
+                as usize,
+            vad: None,
+            segment_samples: None,
+            bit_depth: 16,
+            monitor_only: false,
+            channel_select: None,
+            pre_roll_samples: 0,
+            pcm_events: false,
+        }
+    }
 }
 
 #[derive(serde::Serialize, Clone)]
 struct AudioLevel {
     rms: f32,
     peak: f32,
+    rms_dbfs: f32,
+    peak_dbfs: f32,
+    /// Gain currently applied by automatic gain control, 1.0 when it is off.
+    agc_gain: f32,
+    /// Largest gain reduction applied by the limiter since the previous event,
+    /// in dB. 0 when it did not engage.
+    limiter_reduction_db: f32,
+    /// `rms` and `peak` with meter ballistics applied, for a steadier display;
+    /// see `set_meter_ballistics`.
+    smoothed_rms_dbfs: f32,
+    smoothed_peak_dbfs: f32,
+    /// Peak-hold marker.
+    peak_hold_dbfs: f32,
+    /// "silent", "low", "good", "hot" or "clipping", from `peak_dbfs` and the
+    /// thresholds set with `set_level_thresholds`.
+    level_status: &'static str,
+}
+
+impl AudioLevel {
+    fn new(rms: f32, peak: f32) -> Self {
+        Self {
+            rms,
+            peak,
+            rms_dbfs: audio::linear_to_dbfs(rms),
+            peak_dbfs: audio::linear_to_dbfs(peak),
+            agc_gain: 1.0,
+            limiter_reduction_db: 0.0,
+            smoothed_rms_dbfs: audio::linear_to_dbfs(rms),
+            smoothed_peak_dbfs: audio::linear_to_dbfs(peak),
+            peak_hold_dbfs: audio::linear_to_dbfs(peak),
+            level_status: audio::LevelThresholds::default().status(audio::linear_to_dbfs(peak)),
+        }
+    }
+}
+
+#[derive(serde::Serialize, Clone)]
+struct RecordingStatus {
+    state: &'static str,
+    elapsed_ms: u64,
+    sample_count: usize,
+}
+
+/// Contents of the `<name>.json` sidecar written next to each recording.
+#[derive(serde::Serialize, serde::Deserialize, Clone)]
+struct RecordingMetadata {
+    sample_rate: u32,
+    channels: u16,
+    duration_ms: u64,
+    device_name: Option<String>,
+    /// Milliseconds since the Unix epoch.
+    created_at: u64,
+    peak_dbfs: f32,
+    /// Integrated loudness (ITU-R BS.1770). Only measured for recordings kept
+    /// in memory, and `None` for ones too short or too quiet to gate.
+    #[serde(default)]
+    integrated_lufs: Option<f32>,
+    #[serde(default)]
+    format: OutputFormat,
+    /// Linear gain applied by peak normalization on stop, if any.
+    #[serde(default)]
+    normalization_gain: Option<f32>,
+    /// Whether noise reduction ran. False when it was requested but the
+    /// recording was too short to estimate a noise profile.
+    #[serde(default)]
+    denoised: bool,
+    #[serde(default = "default_bits_per_sample")]
+    bits_per_sample: u16,
+    /// The two channels hold mid and side instead of left and right. The
+    /// original is L = M + S, R = M - S.
+    #[serde(default)]
+    mid_side: bool,
+    /// User labels, trimmed and without duplicates; see
+    /// `recordings::set_recording_tags`.
+    #[serde(default)]
+    tags: Vec<String>,
+    #[serde(default)]
+    note: Option<String>,
+    /// Saved location when the recording was made; left out when none was
+    /// saved.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    location: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    country: Option<String>,
+    /// Exempt from `recordings::prune_recordings`.
+    #[serde(default)]
+    keep: bool,
+}
+
+/// Sidecars written before 24-bit support are all 16-bit.
+fn default_bits_per_sample() -> u16 {
+    16
+}
+
+#[derive(serde::Serialize, Clone)]
+struct SavedRecording {
+    path: String,
+    sidecar_path: String,
+    metadata: RecordingMetadata,
+    /// Every segment file in order, ending with `path`. Empty unless the
+    /// recording was split with `segment_secs`.
+    segments: Vec<String>,
+}
+
+#[derive(serde::Serialize, Clone)]
+struct SegmentSaved {
+    path: String,
+    index: usize,
+}
+
+#[derive(serde::Serialize, Clone)]
+struct DeviceInfo {
+    /// Pass as `device_id` to `start_recording` to pick this exact device; see
+    /// `input_device_id`.
+    id: String,
+    name: String,
+    default_sample_rate: u32,
+    channels: u16,
+    supported_formats: Vec<String>,
+}
+
+/// Default input device and every stream configuration it supports.
+#[derive(serde::Serialize, Clone)]
+struct DeviceConfigInfo {
+    name: String,
+    default_sample_rate: u32,
+    default_channels: u16,
+    supported_configs: Vec<SupportedConfigInfo>,
+}
+
+#[derive(serde::Serialize, Clone)]
+struct SupportedConfigInfo {
+    min_sample_rate: u32,
+    max_sample_rate: u32,
+    channels: u16,
+    sample_format: String,
+}
+
+/// Recording options remembered across launches in `recording_config.json`.
+/// Arguments left out of `start_recording`/`stop_recording` fall back to these.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Default)]
+#[serde(default)]
+struct RecordingConfig {
+    device_name: Option<String>,
+    gain: Option<f32>,
+    channel_mode: Option<ChannelMode>,
+    stream_to_disk: Option<bool>,
+    output_format: Option<OutputFormat>,
+    /// Microphone the last recording actually used, kept to notice when the
+    /// input changes between sessions. Maintained by the recorder, not the UI.
+    last_device_name: Option<String>,
+    /// File name for new recordings, e.g. `rec_{date}_{time}_{location}`; see
+    /// `filename::expand_template`. Unset uses the timestamped default.
+    filename_template: Option<String>,
+    downmix_mode: Option<DownmixMode>,
+    /// Audio kept from before `start_recording`, so push-to-talk doesn't cut
+    /// off the first syllable. Keeps the microphone open between recordings,
+    /// but only once armed with `set_pre_roll_armed`.
+    pre_roll_ms: Option<u32>,
+    opus_bitrate: Option<u32>,
+    quality_preset: Option<QualityPreset>,
+    /// Recordings older than this many days are deleted at startup and by
+    /// `recordings::prune_recordings`. Unset keeps everything.
+    retention_days: Option<u32>,
+    /// Countdown used by `start_recording_with_delay` when no delay is given.
+    start_delay_ms: Option<u32>,
+    /// Settings remembered per microphone, keyed by `DeviceInfo::id` or name.
+    /// Maintained by `set_device_settings`, not the UI.
+    device_settings: BTreeMap<String, DeviceSettings>,
+}
+
+/// Input processing remembered for one microphone and applied whenever a
+/// recording starts on it. Unset fields keep the current setting.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Copy, Default)]
+#[serde(default)]
+struct DeviceSettings {
+    gain: Option<f32>,
+    noise_gate: Option<NoiseGateSettings>,
+    highpass_cutoff_hz: Option<f32>,
+}
+
+impl DeviceSettings {
+    fn validate(&self) -> Result<(), String> {
+        if let Some(gain) = self.gain {
+            if !gain.is_finite() || gain < 0.0 {
+                return Err(format!("Invalid input gain: {}", gain));
+            }
+        }
+        if let Some(threshold) = self.noise_gate.and_then(|gate| gate.threshold_dbfs) {
+            if !threshold.is_finite() || threshold > 0.0 {
+                return Err(format!("Invalid noise gate threshold: {} dBFS", threshold));
+            }
+        }
+        if let Some(cutoff_hz) = self.highpass_cutoff_hz {
+            if !cutoff_hz.is_finite() || cutoff_hz < 0.0 {
+                return Err(format!("Invalid high-pass cutoff: {}", cutoff_hz));
+            }
+        }
+        Ok(())
+    }
+
+    fn apply(&self, state: &RecordingState) {
+        if let Some(gain) = self.gain {
+            *state.gain.lock_or_recover() = gain;
+        }
+        if let Some(noise_gate) = self.noise_gate {
+            *state.noise_gate.lock_or_recover() = noise_gate;
+        }
+        if let Some(cutoff_hz) = self.highpass_cutoff_hz {
+            *state.highpass_cutoff.lock_or_recover() = cutoff_hz;
+        }
+    }
+}
+
+/// Payload of `device-selected`, emitted whenever a microphone recording
+/// starts.
+#[derive(serde::Serialize, Clone)]
+struct DeviceSelected {
+    name: String,
+    previous: Option<String>,
+    changed: bool,
+}
+
+/// Payload of `recording-config`, emitted once a recording has started with
+/// what it actually runs at, which depends on the device as much as on the
+/// requested options.
+#[derive(serde::Serialize, Clone)]
+struct ActiveRecordingConfig {
+    sample_rate: u32,
+    /// Channels being recorded, after the channel mode is applied.
+    channels: u16,
+    /// Sample format the device delivers, e.g. "f32" or "i16".
+    sample_format: String,
+    device_name: String,
+}
+
+/// Payload of `device-changed`: the previously used microphone is gone.
+/// `current` is the device now in use, if a recording just started.
+#[derive(serde::Serialize, Clone)]
+struct DeviceChanged {
+    previous: String,
+    current: Option<String>,
+}
+
+impl RecordingConfig {
+    fn start_options(&self) -> StartOptions {
+        let preset = self
+            .quality_preset
+            .map(QualityPreset::start_options)
+            .unwrap_or_default();
+        let stream_to_disk = self.stream_to_disk.unwrap_or(false);
+        StartOptions {
+            device_name: self.device_name.clone(),
+            stream_to_disk,
+            channel_mode: self.channel_mode.unwrap_or(preset.channel_mode),
+            downmix_mode: self.downmix_mode.unwrap_or_default(),
+            // Recordings above 16 bits can't be streamed to disk.
+            bit_depth: preset.bit_depth.filter(|_| !stream_to_disk),
+            ..preset
+        }
+    }
+
+    fn stop_options(&self) -> StopOptions {
+        let preset = self
+            .quality_preset
+            .map(QualityPreset::stop_options)
+            .unwrap_or_default();
+        StopOptions {
+            format: self.output_format.unwrap_or(preset.format),
+            opus_bitrate: self.opus_bitrate,
+            ..preset
+        }
+    }
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, Default)]
+struct AppSettings {
+    #[serde(default)]
+    recordings_dir: Option<PathBuf>,
+    /// Accelerator such as "Alt+E" or "Ctrl+Shift+Space".
+    #[serde(default)]
+    shortcut: Option<String>,
+    #[serde(default)]
+    shortcut_mode: ShortcutMode,
+    /// Extra accelerators bound to a single action each, e.g.
+    /// `{"Alt+Shift+E": "cancel"}`. The main shortcut is configured separately.
+    #[serde(default)]
+    action_shortcuts: BTreeMap<String, ShortcutAction>,
+}
+
+/// What an entry in `AppSettings::action_shortcuts` does when pressed.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+enum ShortcutAction {
+    Start,
+    Stop,
+    Cancel,
+    /// Start when idle, stop when recording.
+    Toggle,
+}
+
+/// How the global shortcut drives recording.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+enum ShortcutMode {
+    /// Show the window and let the frontend start/stop on each press.
+    #[default]
+    Toggle,
+    /// Record while the shortcut is held and save on release.
+    PushToTalk,
 }
 
 #[derive(serde::Serialize, serde::Deserialize, Clone)]
 struct LocationSettings {
     location: String,
+    #[serde(default)]
     country: Option<String>,
+    /// IANA zone such as "America/New_York".
+    #[serde(default)]
+    timezone: Option<String>,
+}
+
+#[derive(serde::Serialize, Clone)]
+struct ClippingDetected {
+    /// Samples that hit full scale since the previous event.
+    count: u64,
+}
+
+#[derive(serde::Serialize, Clone)]
+struct NoiseGateEvent {
+    /// False while the gate is muting the input.
+    open: bool,
+}
+
+/// Payload of `mute-changed`.
+#[derive(serde::Serialize, Clone)]
+struct MuteEvent {
+    muted: bool,
+}
+
+/// Payload of `mic-live`, emitted when the monitor or pre-roll stream opens
+/// or closes the microphone outside a recording.
+#[derive(serde::Serialize, Clone)]
+struct MicLive {
+    live: bool,
+}
+
+#[derive(serde::Serialize, Clone)]
+struct StreamErrorPayload {
+    message: String,
+    /// The device went away and the recording was stopped.
+    fatal: bool,
+}
+
+/// Builds the cpal error callback. Errors are forwarded to the UI as
+/// `stream-error`; losing the device also stops the recording so the state
+/// does not keep pointing at a dead stream.
+fn stream_error_handler(
+    recording: &RecordingState,
+    app: &AppHandle,
+) -> impl FnMut(cpal::StreamError) + Send + 'static {
+    let recording = recording.clone();
+    let app = app.clone();
+    move |err| {
+        error!("an error occurred on stream: {}", err);
+        let fatal = matches!(err, cpal::StreamError::DeviceNotAvailable);
+        let payload = StreamErrorPayload {
+            message: err.to_string(),
+            fatal,
+        };
+        if let Err(e) = app.emit("stream-error", payload) {
+            error!("failed to emit stream-error: {:?}", e);
+        }
+        if fatal && !recording.auto_stopping.swap(true, Ordering::SeqCst) {
+            finish_on_worker(&recording, &app, "recording-saved");
+        }
+    }
+}
+
+/// Whether the warm-up of `start_recording_with_delay` is still running. The
+/// first callback after it ends restarts the recording clock.
+fn warming_up(recording: &RecordingState) -> bool {
+    let mut warmup_until = recording.warmup_until.lock_or_recover();
+    match *warmup_until {
+        Some(until) if Instant::now() < until => true,
+        Some(_) => {
+            *warmup_until = None;
+            *recording.started_at.lock_or_recover() = Some(Instant::now());
+            false
+        }
+        None => false,
+    }
+}
+
+fn process_input_f32(
+    data: &[f32],
+    capture: &CaptureSettings,
+    recording: &RecordingState,
+    app: &AppHandle,
+) {
+    process_input(data, capture, recording, app, |sample| sample);
+}
+
+fn process_input_i16(
+    data: &[i16],
+    capture: &CaptureSettings,
+    recording: &RecordingState,
+    app: &AppHandle,
+) {
+    process_input(data, capture, recording, app, |sample| {
+        sample as f32 / i16::MAX as f32
+    });
+}
+
+fn process_input_u16(
+    data: &[u16],
+    capture: &CaptureSettings,
+    recording: &RecordingState,
+    app: &AppHandle,
+) {
+    process_input(data, capture, recording, app, |sample| {
+        (sample as f32 / u16::MAX as f32) * 2.0 - 1.0
+    });
+}
+
+/// Buffers the system-audio side of a mixed recording. It only follows the
+/// channel layout and pause state; gain, filtering and metering belong to the
+/// microphone.
+fn process_loopback<T: Copy>(
+    data: &[T],
+    channels: usize,
+    channel_mode: ChannelMode,
+    recording: &RecordingState,
+    normalize: impl Fn(T) -> f32,
+) {
+    if data.is_empty()
+        || channels == 0
+        || recording.auto_stopping.load(Ordering::Relaxed)
+        || recording.paused.load(Ordering::Relaxed)
+        || warming_up(recording)
+    {
+        return;
+    }
+
+    // Muting redacts the whole recording, so the system audio goes silent too.
+    let muted = recording.muted.load(Ordering::Relaxed);
+    let normalize = |sample: T| if muted { 0.0 } else { normalize(sample) };
+    let downmix_weights = audio::downmix_weights(channels);
+    let downmix_weight_sum = audio::downmix_weight_sum(channels);
+    let mut buffer = recording.loopback_samples.lock_or_recover();
+    for frame in data.chunks_exact(channels) {
+        match channel_mode {
+            ChannelMode::Mono => {
+                let mono: f32 = frame
+                    .iter()
+                    .enumerate()
+                    .map(|(index, &sample)| {
+                        normalize(sample).clamp(-1.0, 1.0)
+                            * downmix_weights.map_or(1.0, |weights| weights[index])
+                    })
+                    .sum();
+                buffer.push(to_i16(mono / downmix_weight_sum));
+            }
+            ChannelMode::Stereo => {
+                let left = normalize(frame[0]);
+                let right = frame.get(1).map_or(left, |&sample| normalize(sample));
+                buffer.push(to_i16(left));
+                buffer.push(to_i16(right));
+            }
+        }
+    }
+}
+
+fn build_loopback_stream<T: cpal::SizedSample + Send + 'static>(
+    device: &cpal::Device,
+    config: &cpal::StreamConfig,
+    channel_mode: ChannelMode,
+    recording: &RecordingState,
+    app: &AppHandle,
+    normalize: impl Fn(T) -> f32 + Send + 'static,
+) -> Result<cpal::Stream, cpal::BuildStreamError> {
+    let on_error = stream_error_handler(recording, app);
+    let recording = recording.clone();
+    let channels = config.channels as usize;
+    device.build_input_stream(
+        config,
+        move |data: &[T], _: &_| {
+            process_loopback(data, channels, channel_mode, &recording, &normalize);
+        },
+        on_error,
+        None,
+    )
+}
+
+/// Opens the system-audio half of a mixed recording on the default output
+/// device. The stream is returned unstarted.
+fn open_loopback_stream(
+    host: &cpal::Host,
+    state: &RecordingState,
+    app: &AppHandle,
+    channel_mode: ChannelMode,
+) -> Result<cpal::Stream, String> {
+    let device = find_loopback_device(host, None)?;
+    let supported_config = device.default_output_config().map_err(|e| e.to_string())?;
+    let sample_format = supported_config.sample_format();
+    let config: cpal::StreamConfig = supported_config.into();
+    if config.channels == 0 || config.sample_rate.0 == 0 {
+        return Err("loopback device reported an invalid stream config".to_string());
+    }
+    info!(device = %device.name().unwrap_or_default(), "Using loopback device");
+
+    let stream = match sample_format {
+        SampleFormat::F32 => {
+            build_loopback_stream::<f32>(&device, &config, channel_mode, state, app, |s| s)
+        }
+        SampleFormat::I16 => {
+            build_loopback_stream::<i16>(&device, &config, channel_mode, state, app, |s| {
+                s as f32 / i16::MAX as f32
+            })
+        }
+        SampleFormat::U16 => {
+            build_loopback_stream::<u16>(&device, &config, channel_mode, state, app, |s| {
+                (s as f32 / u16::MAX as f32) * 2.0 - 1.0
+            })
+        }
+        _ => return Err("Unsupported loopback sample format".to_string()),
+    }
+    .map_err(|e| e.to_string())?;
+    *state.loopback_rate.lock_or_recover() = config.sample_rate.0;
+    Ok(stream)
+}
+
+fn process_input<T: Copy>(
+    data: &[T],
+    capture: &CaptureSettings,
+    recording: &RecordingState,
+    app: &AppHandle,
+    normalize: impl Fn(T) -> f32,
+) {
+    let channels = capture.channels;
+    if data.is_empty() || channels == 0 || recording.auto_stopping.load(Ordering::Relaxed) {
+        return;
+    }
+    recording
+        .stream_info
+        .lock_or_recover()
+        .record_callback(data.len() / channels);
+
+    if warming_up(recording) {
+        report_level(recording, app, 0.0, data.len(), 0.0, 1.0, 1.0);
+        return;
+    }
+    // While paused the stream keeps running, but frames are dropped and the
+    // meter reads zero so the UI can show a paused state.
+    if recording.paused.load(Ordering::Relaxed) {
+        report_level(recording, app, 0.0, data.len(), 0.0, 1.0, 1.0);
+        return;
+    }
+
+    // While muted, frames are still stored, but as silence, so the recording
+    // keeps its length and the meter reads zero.
+    let muted = recording.muted.load(Ordering::Relaxed);
+    let gain = *recording.gain.lock_or_recover();
+    let cutoff = *recording.highpass_cutoff.lock_or_recover();
+    let coefficient = audio::highpass_coefficient(cutoff, capture.sample_rate);
+    let mut highpass = if cutoff > 0.0 {
+        Some(recording.highpass.lock_or_recover())
+    } else {
+        None
+    };
+    let mut sum_squares = 0.0f32;
+    let mut peak = 0.0f32;
+    let output_channels = capture.channel_mode.output_channels() as usize;
+    let mut recorded = Vec::with_capacity(data.len() / channels * output_channels);
+    let mut waveform = Vec::new();
+    let mut bucket = recording.waveform_bucket.lock_or_recover();
+    // Channels actually processed per frame: just the selected one, if any.
+    let frame_channels = if capture.channel_select.is_some() {
+        1
+    } else {
+        channels
+    };
+    let downmix_weights = audio::downmix_weights(frame_channels);
+    let downmix_weight_sum = audio::downmix_weight_sum(frame_channels);
+    let mut speaking = recording.speaking.load(Ordering::Relaxed);
+    let mut quiet_frames = recording.vad_quiet_frames.load(Ordering::Relaxed);
+    let agc_settings = *recording.agc.lock_or_recover();
+    let agc_params = audio::AgcParams {
+        target: audio::dbfs_to_linear(agc_settings.target_dbfs),
+        noise_floor: audio::dbfs_to_linear(AGC_NOISE_FLOOR_DBFS),
+        attack: audio::smoothing_coefficient(AGC_ATTACK_MS, capture.sample_rate),
+        release: audio::smoothing_coefficient(AGC_RELEASE_MS, capture.sample_rate),
+        max_gain: AGC_MAX_GAIN,
+    };
+    let mut agc = if agc_settings.enabled {
+        Some(recording.agc_state.lock_or_recover())
+    } else {
+        None
+    };
+    let limiter_settings = *recording.limiter.lock_or_recover();
+    let limiter_params = audio::LimiterParams {
+        threshold: audio::dbfs_to_linear(limiter_settings.threshold_dbfs),
+        release: audio::smoothing_coefficient(limiter_settings.release_ms, capture.sample_rate),
+    };
+    let mut limiter = if limiter_settings.enabled {
+        Some(recording.limiter_state.lock_or_recover())
+    } else {
+        None
+    };
+    let gate_settings = *recording.noise_gate.lock_or_recover();
+    let gate_params = gate_settings
+        .threshold_dbfs
+        .map(|threshold_dbfs| audio::NoiseGateParams {
+            open_threshold: audio::dbfs_to_linear(threshold_dbfs),
+            close_threshold: audio::dbfs_to_linear(threshold_dbfs - GATE_HYSTERESIS_DB),
+            hold_frames: gate_settings.hold_ms * capture.sample_rate as u64 / 1000,
+            attack: audio::smoothing_coefficient(GATE_ATTACK_MS, capture.sample_rate),
+            release: audio::smoothing_coefficient(GATE_RELEASE_MS, capture.sample_rate),
+        });
+    let mut gate = gate_params
+        .as_ref()
+        .map(|_| recording.noise_gate_state.lock_or_recover());
+    let gate_was_open = gate.as_ref().map(|gate| gate.is_open());
+    let mut min_limiter_gain = 1.0f32;
+    let mut clipped = 0usize;
+    let keep_hires = capture.bit_depth > 16;
+    // A float recording keeps only the unquantized copy; see
+    // `take_recorded_samples`.
+    let hires_only = capture.bit_depth == 32;
+    let mut hires = Vec::new();
+    let mut frame_values = Vec::with_capacity(frame_channels);
+
+    for frame in data.chunks(channels) {
+        if frame.len() < channels {
+            break;
+        }
+        let frame = match capture.channel_select {
+            Some(index) => &frame[index..=index],
+            None => frame,
+        };
+        let mut mono = 0.0f32;
+        let mut left = 0.0f32;
+        let mut right = 0.0f32;
+        // Before the clamp, for float recordings that keep levels above 0 dBFS.
+        let mut unclamped = [0.0f32; 3];
+        let mut frame_peak = 0.0f32;
+        // The AGC gain from the previous frame is applied to this one, and this
+        // frame's pre-AGC power then updates it.
+        let agc_gain = agc.as_ref().map_or(1.0, |agc| agc.gain());
+        let mut frame_power = 0.0f32;
+        frame_values.clear();
+        for (index, &sample) in frame.iter().enumerate() {
+            let mut value = if muted { 0.0 } else { normalize(sample) };
+            if let Some(filter) = highpass.as_mut() {
+                value = filter.process(index, value, coefficient);
+            }
+            value *= gain;
+            frame_power += value * value;
+            frame_values.push(value * agc_gain);
+        }
+        // The gate and limiter look at the whole frame so all channels get
+        // the same gain; the clamp only catches what the limiter lets through.
+        if let (Some(gate), Some(params)) = (gate.as_mut(), gate_params.as_ref()) {
+            let frame_max = frame_values.iter().fold(0.0f32, |max, v| max.max(v.abs()));
+            let gate_gain = gate.process(frame_max, params);
+            frame_values
+                .iter_mut()
+                .for_each(|value| *value *= gate_gain);
+        }
+        let limiter_gain = match limiter.as_mut() {
+            Some(limiter) => {
+                let frame_max = frame_values.iter().fold(0.0f32, |max, v| max.max(v.abs()));
+                limiter.process(frame_max, &limiter_params)
+            }
+            None => 1.0,
+        };
+        min_limiter_gain = min_limiter_gain.min(limiter_gain);
+        for (index, &value) in frame_values.iter().enumerate() {
+            let value = value * limiter_gain;
+            // Counted on what is about to be stored, once per sample.
+            if value.abs() >= 1.0 {
+                clipped += 1;
+            }
+            let clamped = value.clamp(-1.0, 1.0);
+            let abs_sample = clamped.abs();
+            frame_peak = frame_peak.max(abs_sample);
+            if abs_sample > peak {
+                peak = abs_sample;
+            }
+            sum_squares += clamped * clamped;
+            let weight = downmix_weights.map_or(1.0, |weights| weights[index]);
+            mono += clamped * weight;
+            unclamped[0] += value * weight;
+            match index {
+                0 => {
+                    left = clamped;
+                    right = clamped;
+                    unclamped[1] = value;
+                    unclamped[2] = value;
+                }
+                1 => {
+                    right = clamped;
+                    unclamped[2] = value;
+                }
+                _ => {}
+            }
+        }
+        if let Some(agc) = agc.as_mut() {
+            agc.update(frame_power / frame_channels as f32, &agc_params);
+        }
+        let keep_frame = if capture.monitor_only {
+            capture.pre_roll_samples > 0
+        } else {
+            match capture.vad {
+                Some(vad) => {
+                    update_vad(vad, frame_peak, &mut speaking, &mut quiet_frames, app);
+                    speaking
+                }
+                None => true,
+            }
+        };
+        if keep_frame {
+            if !hires_only {
+                match capture.channel_mode {
+                    ChannelMode::Mono => recorded.push(to_i16(mono / downmix_weight_sum)),
+                    ChannelMode::Stereo => {
+                        recorded.push(to_i16(left));
+                        recorded.push(to_i16(right));
+                    }
+                }
+            }
+            if keep_hires {
+                match capture.channel_mode {
+                    ChannelMode::Mono => hires.push(unclamped[0] / downmix_weight_sum),
+                    ChannelMode::Stereo => hires.extend([unclamped[1], unclamped[2]]),
+                }
+            }
+        }
+
+        bucket.peak = bucket.peak.max(frame_peak);
+        bucket.frames += 1;
+        if bucket.frames >= capture.waveform_bucket_frames {
+            waveform.push(bucket.peak);
+            *bucket = WaveformBucket::default();
+        }
+    }
+    drop(bucket);
+    drop(highpass);
+    let agc_gain = agc.as_ref().map_or(1.0, |agc| agc.gain());
+    drop(agc);
+    drop(limiter);
+    let gate_open = gate.as_ref().map(|gate| gate.is_open());
+    drop(gate);
+    if let Some(open) = gate_open.filter(|_| gate_open != gate_was_open) {
+        let _ = app.emit("noise-gate", NoiseGateEvent { open });
+    }
+    recording.speaking.store(speaking, Ordering::Relaxed);
+    recording
+        .vad_quiet_frames
+        .store(quiet_frames, Ordering::Relaxed);
+
+    // A monitor only meters; the peak and the silence warning belong to the
+    // next recording. Silence the user asked for shouldn't trip the warning.
+    if !capture.monitor_only {
+        let mut global_peak = recording.peak.lock_or_recover();
+        *global_peak = global_peak.max(peak);
+        drop(global_peak);
+        if !muted {
+            detect_silence(peak, data.len() / channels, capture, recording, app);
+        }
+    }
+
+    let mut limit_reached = false;
+    if let Some(max_samples) = capture.max_samples {
+        let captured = recording.captured_samples.load(Ordering::Relaxed);
+        let remaining = max_samples.saturating_sub(captured) as usize;
+        if recorded.len().max(hires.len()) >= remaining {
+            recorded.truncate(remaining);
+            hires.truncate(remaining);
+            limit_reached = true;
+        }
+    }
+    let stored = recorded.len().max(hires.len());
+    if capture.pcm_events && hires_only && !hires.is_empty() {
+        let pcm: Vec<i16> = hires.iter().map(|&sample| to_i16(sample)).collect();
+        emit_pcm_chunk(recording, capture, app, &pcm);
+    }
+    if !hires.is_empty() {
+        recording.hires_samples.lock_or_recover().extend(hires);
+    }
+    if capture.monitor_only {
+        push_pre_roll(recording, capture, recorded);
+    } else {
+        recording
+            .captured_samples
+            .fetch_add(stored as u64, Ordering::Relaxed);
+        if capture.pcm_events && !recorded.is_empty() {
+            emit_pcm_chunk(recording, capture, app, &recorded);
+        }
+        store_samples(recording, capture, app, recorded);
+    }
+
+    report_level(
+        recording,
+        app,
+        sum_squares,
+        data.len() / channels * frame_channels,
+        peak,
+        agc_gain,
+        min_limiter_gain,
+    );
+    if clipped > 0 {
+        report_clipping(recording, app, clipped);
+    }
+    if !waveform.is_empty() {
+        let _ = app.emit("waveform-frame", waveform);
+    }
+
+    if limit_reached && !recording.auto_stopping.swap(true, Ordering::SeqCst) {
+        finish_on_worker(recording, app, "recording-auto-stopped");
+    }
+}
+
+/// Payload of `pcm-chunk`: one buffer of recorded audio, interleaved i16
+/// little-endian and base64-encoded. `sequence` counts up from 0 for each
+/// recording, so a gap means chunks were lost.
+#[derive(serde::Serialize, Clone)]
+struct PcmChunk {
+    sequence: u64,
+    sample_rate: u32,
+    channels: u16,
+    data: String,
+}
+
+fn emit_pcm_chunk(
+    recording: &RecordingState,
+    capture: &CaptureSettings,
+    app: &AppHandle,
+    samples: &[i16],
+) {
+    let bytes: Vec<u8> = samples.iter().flat_map(|s| s.to_le_bytes()).collect();
+    let chunk = PcmChunk {
+        sequence: recording.pcm_sequence.fetch_add(1, Ordering::Relaxed),
+        sample_rate: capture.sample_rate,
+        channels: capture.channel_mode.output_channels(),
+        data: base64::engine::general_purpose::STANDARD.encode(bytes),
+    };
+    if let Err(e) = app.emit("pcm-chunk", chunk) {
+        error!("failed to emit pcm-chunk: {:?}", e);
+    }
+}
+
+/// Adds one buffer's levels to the meter and emits `audio-level` once the
+/// configured interval has passed. The event carries the highest peak and the
+/// RMS over every sample since the previous one, so short transients between
+/// emits still show up.
+fn report_level(
+    recording: &RecordingState,
+    app: &AppHandle,
+    sum_squares: f32,
+    samples: usize,
+    peak: f32,
+    agc_gain: f32,
+    limiter_gain: f32,
+) {
+    let interval = *recording.level_interval.lock_or_recover();
+    let mut meter = recording.level_meter.lock_or_recover();
+    meter.sum_squares += sum_squares;
+    meter.samples += samples;
+    meter.peak = meter.peak.max(peak);
+    meter.limiter_reduction_db = meter
+        .limiter_reduction_db
+        .max(-audio::linear_to_dbfs(limiter_gain));
+
+    let now = Instant::now();
+    if meter
+        .last_emit
+        .is_some_and(|last| now.duration_since(last) < interval)
+    {
+        return;
+    }
+    let rms = (meter.sum_squares / meter.samples.max(1) as f32).sqrt();
+    let elapsed = meter
+        .last_emit
+        .map_or(interval, |last| now.duration_since(last));
+    let ballistics = *recording.meter_ballistics.lock_or_recover();
+    let mut smoothed = recording.level_ballistics.lock_or_recover();
+    smoothed.update(rms, meter.peak, elapsed.as_secs_f32() * 1000.0, &ballistics);
+    let level = AudioLevel {
+        agc_gain,
+        limiter_reduction_db: meter.limiter_reduction_db,
+        smoothed_rms_dbfs: audio::linear_to_dbfs(smoothed.rms),
+        smoothed_peak_dbfs: audio::linear_to_dbfs(smoothed.peak),
+        peak_hold_dbfs: audio::linear_to_dbfs(smoothed.held_peak),
+        level_status: recording
+            .level_thresholds
+            .lock_or_recover()
+            .status(audio::linear_to_dbfs(meter.peak)),
+        ..AudioLevel::new(rms, meter.peak)
+    };
+    drop(smoothed);
+    *meter = LevelMeter {
+        last_emit: Some(now),
+        ..LevelMeter::default()
+    };
+    drop(meter);
+    let _ = app.emit("audio-level", level);
+}
+
+/// Adds to the clipped-sample count and emits `clipping-detected` at most
+/// once per `CLIP_EVENT_INTERVAL`. Only called for buffers that clipped.
+fn report_clipping(recording: &RecordingState, app: &AppHandle, clipped: usize) {
+    let mut meter = recording.clip_meter.lock_or_recover();
+    meter.clipped += clipped as u64;
+    let now = Instant::now();
+    if meter
+        .last_emit
+        .is_some_and(|last| now.duration_since(last) < CLIP_EVENT_INTERVAL)
+    {
+        return;
+    }
+    let event = ClippingDetected {
+        count: meter.clipped,
+    };
+    *meter = ClipMeter {
+        last_emit: Some(now),
+        clipped: 0,
+    };
+    drop(meter);
+    let _ = app.emit("clipping-detected", event);
+}
+
+/// Finishes the recording from a stream callback and emits `event` with the
+/// saved path. Finalizing drops the cpal stream, which must not happen on the
+/// audio thread itself, so it is handed off to a short-lived worker.
+fn finish_on_worker(recording: &RecordingState, app: &AppHandle, event: &'static str) {
+    let recording = recording.clone();
+    let app = app.clone();
+    std::thread::spawn(move || {
+        match finish_recording(&recording, &app, saved_stop_options(&app)) {
+            Ok(saved) => {
+                if let Err(e) = app.emit(event, saved.path) {
+                    error!("failed to emit {}: {:?}", event, e);
+                }
+            }
+            Err(e) => error!("failed to stop recording: {}", e),
+        }
+        resume_pre_roll(&recording, &app);
+    });
+}
+
+/// Advances the voice-activity gate by one frame, emitting `speech-started`
+/// and `speech-ended` on transitions. Frames in the hangover still count as
+/// speech so word endings are not clipped.
+fn update_vad(
+    vad: VadGate,
+    frame_peak: f32,
+    speaking: &mut bool,
+    quiet_frames: &mut u64,
+    app: &AppHandle,
+) {
+    if frame_peak >= vad.onset {
+        *quiet_frames = 0;
+        if !*speaking {
+            *speaking = true;
+            let _ = app.emit("speech-started", ());
+        }
+    } else if *speaking {
+        *quiet_frames += 1;
+        if *quiet_frames >= vad.hangover_frames {
+            *speaking = false;
+            *quiet_frames = 0;
+            let _ = app.emit("speech-ended", ());
+        }
+    }
+}
+
+/// Emits `silence-detected` once per stretch of input that stays below the
+/// configured threshold for longer than the timeout.
+fn detect_silence(
+    peak: f32,
+    frames: usize,
+    capture: &CaptureSettings,
+    recording: &RecordingState,
+    app: &AppHandle,
+) {
+    let silence = *recording.silence.lock_or_recover();
+
+    if audio::linear_to_dbfs(peak) >= silence.threshold_dbfs {
+        recording.silent_frames.store(0, Ordering::Relaxed);
+        recording.silence_reported.store(false, Ordering::Relaxed);
+        return;
+    }
+
+    let silent_frames = recording
+        .silent_frames
+        .fetch_add(frames as u64, Ordering::Relaxed)
+        + frames as u64;
+    let silent_ms = silent_frames * 1000 / capture.sample_rate.max(1) as u64;
+    if silent_ms >= silence.timeout_ms && !recording.silence_reported.swap(true, Ordering::Relaxed)
+    {
+        let _ = app.emit("silence-detected", SilenceDetected { silent_ms });
+    }
+}
+
+fn to_i16(sample: f32) -> i16 {
+    (sample.clamp(-1.0, 1.0) * i16::MAX as f32) as i16
+}
+
+/// Hands captured samples to the incremental WAV writer when one is open,
+/// otherwise keeps them in memory until `stop_recording`.
+fn store_samples(
+    recording: &RecordingState,
+    capture: &CaptureSettings,
+    app: &AppHandle,
+    samples: Vec<i16>,
+) {
+    {
+        let mut writer_guard = recording.writer.lock_or_recover();
+        if writer_guard.is_some() {
+            // Fill the current segment up to its limit, rotate, and carry the
+            // rest of the buffer into the next one so nothing is dropped.
+            let mut remaining = &samples[..];
+            while !remaining.is_empty() {
+                let Some(writer) = writer_guard.as_mut() else {
+                    return;
+                };
+                let take = match capture.segment_samples {
+                    Some(limit) => {
+                        let written = recording.segment_written.load(Ordering::Relaxed);
+                        (limit.saturating_sub(written) as usize).min(remaining.len())
+                    }
+                    None => remaining.len(),
+                };
+                for &sample in &remaining[..take] {
+                    if let Err(e) = writer.write_sample(sample) {
+                        error!("failed to write sample: {}", e);
+                        return;
+                    }
+                }
+                remaining = &remaining[take..];
+
+                if let Some(limit) = capture.segment_samples {
+                    let written = recording
+                        .segment_written
+                        .fetch_add(take as u64, Ordering::Relaxed)
+                        + take as u64;
+                    if written >= limit {
+                        if let Err(e) = rotate_segment(recording, &mut writer_guard, capture, app) {
+                            error!("failed to start next segment: {}", e);
+                            return;
+                        }
+                    }
+                }
+            }
+            return;
+        }
+    }
+
+    recording.samples.lock_or_recover().extend(samples);
+}
+
+/// Interleaved samples the recording in progress holds in memory.
+fn recorded_len(state: &RecordingState) -> usize {
+    let samples = state.samples.lock_or_recover().len();
+    samples.max(state.hires_samples.lock_or_recover().len())
+}
+
+/// 16-bit copy of the in-memory recording from sample `start` on. A float
+/// recording only keeps `hires_samples`, which are quantized here.
+fn recorded_samples_from(state: &RecordingState, start: usize) -> Vec<i16> {
+    if *state.bit_depth.lock_or_recover() == 32 {
+        let hires = state.hires_samples.lock_or_recover();
+        return hires
+            .get(start..)
+            .unwrap_or_default()
+            .iter()
+            .map(|&sample| to_i16(sample))
+            .collect();
+    }
+    let samples = state.samples.lock_or_recover();
+    samples.get(start..).unwrap_or_default().to_vec()
+}
+
+/// Takes the in-memory recording as 16-bit samples, like
+/// `recorded_samples_from`. `hires_samples` is left for the caller.
+fn take_recorded_samples(state: &RecordingState) -> Vec<i16> {
+    let samples = std::mem::take(&mut *state.samples.lock_or_recover());
+    if *state.bit_depth.lock_or_recover() == 32 {
+        let hires = state.hires_samples.lock_or_recover();
+        return hires.iter().map(|&sample| to_i16(sample)).collect();
+    }
+    samples
+}
+
+/// Appends monitor audio to the pre-roll buffer, dropping the oldest samples
+/// beyond `capture.pre_roll_samples`.
+fn push_pre_roll(recording: &RecordingState, capture: &CaptureSettings, samples: Vec<i16>) {
+    if samples.is_empty() {
+        return;
+    }
+    let mut pre_roll = recording.pre_roll.lock_or_recover();
+    pre_roll.samples.extend(samples);
+    let excess = pre_roll
+        .samples
+        .len()
+        .saturating_sub(capture.pre_roll_samples);
+    pre_roll.samples.drain(..excess);
+}
+
+/// Clears everything left over from the previous recording before a new stream
+/// starts.
+fn reset_capture_state(
+    state: &RecordingState,
+    capture: &CaptureSettings,
+    device_name: String,
+    requested_path: Option<PathBuf>,
+) {
+    *state.sample_rate.lock_or_recover() = capture.sample_rate;
+    *state.output_channels.lock_or_recover() = capture.channel_mode.output_channels();
+    state.samples.lock_or_recover().clear();
+    state.paused.store(false, Ordering::Relaxed);
+    state.muted.store(false, Ordering::Relaxed);
+    *state.warmup_until.lock_or_recover() = None;
+    state.captured_samples.store(0, Ordering::Relaxed);
+    state.pcm_sequence.store(0, Ordering::Relaxed);
+    state.auto_stopping.store(false, Ordering::Relaxed);
+    *state.peak.lock_or_recover() = 0.0;
+    state.silent_frames.store(0, Ordering::Relaxed);
+    state.silence_reported.store(false, Ordering::Relaxed);
+    state.speaking.store(false, Ordering::Relaxed);
+    *state.agc_state.lock_or_recover() = audio::AutomaticGain::default();
+    *state.limiter_state.lock_or_recover() = audio::Limiter::default();
+    *state.noise_gate_state.lock_or_recover() = audio::NoiseGate::default();
+    state.vad_quiet_frames.store(0, Ordering::Relaxed);
+    *state.waveform_bucket.lock_or_recover() = WaveformBucket::default();
+    state.highpass.lock_or_recover().reset(capture.channels);
+    *state.device_name.lock_or_recover() = Some(device_name);
+
+    *state.requested_path.lock_or_recover() = requested_path;
+    state.segments.lock_or_recover().clear();
+    state.segment_written.store(0, Ordering::Relaxed);
+    *state.segment_base.lock_or_recover() = None;
+    state.loopback_samples.lock_or_recover().clear();
+    *state.loopback_rate.lock_or_recover() = 0;
+    state.hires_samples.lock_or_recover().clear();
+    *state.bit_depth.lock_or_recover() = capture.bit_depth;
+    *state.level_meter.lock_or_recover() = LevelMeter::default();
+    *state.level_ballistics.lock_or_recover() = audio::LevelBallistics::default();
+    *state.clip_meter.lock_or_recover() = ClipMeter::default();
+}
+
+/// Finalizes the current segment, announces it with `segment-saved`, and
+/// opens the next one in its place.
+fn rotate_segment(
+    recording: &RecordingState,
+    writer_slot: &mut Option<FileWavWriter>,
+    capture: &CaptureSettings,
+    app: &AppHandle,
+) -> Result<(), String> {
+    let Some(writer) = writer_slot.take() else {
+        return Ok(());
+    };
+    writer.finalize().map_err(|e| e.to_string())?;
+
+    let base = recording
+        .segment_base
+        .lock_or_recover()
+        .clone()
+        .ok_or("Segment base path missing")?;
+    let mut path_guard = recording.output_path.lock_or_recover();
+    let finished = path_guard.take().ok_or("Recording output path missing")?;
+    let mut segments = recording.segments.lock_or_recover();
+    segments.push(finished.clone());
+    let index = segments.len() - 1;
+
+    let next = segment_path(&base, segments.len());
+    let spec = wav_spec(capture.sample_rate, capture.channel_mode.output_channels());
+    *writer_slot = Some(create_wav_writer(&next, spec)?);
+    *path_guard = Some(next);
+    recording.segment_written.store(0, Ordering::Relaxed);
+
+    let saved = SegmentSaved {
+        path: finished.to_string_lossy().to_string(),
+        index,
+    };
+    if let Err(e) = app.emit("segment-saved", saved) {
+        error!("failed to emit segment-saved: {:?}", e);
+    }
+    Ok(())
+}
+
+/// Path of the zero-based segment `index`, e.g. `recording_part001.wav`.
+fn segment_path(base: &Path, index: usize) -> PathBuf {
+    let stem = base
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_default();
+    base.with_file_name(format!(
+        "{stem}_part{:03}.{}",
+        index + 1,
+        OutputFormat::Wav.extension()
+    ))
+}
+
+fn recording_status(recording: &RecordingState, state: &'static str) -> RecordingStatus {
+    let elapsed_ms = recording
+        .started_at
+        .lock_or_recover()
+        .map(|started| started.elapsed().as_millis() as u64)
+        .unwrap_or(0);
+
+    let streamed_count = recording
+        .writer
+        .lock_or_recover()
+        .as_ref()
+        .map(|writer| writer.len() as usize);
+    let sample_count = match streamed_count {
+        Some(count) => count,
+        None => recorded_len(recording),
+    };
+
+    RecordingStatus {
+        state,
+        elapsed_ms,
+        sample_count,
+    }
+}
+
+/// Single source of truth for the frontend's view of the recorder.
+fn emit_recording_state(app: &AppHandle, status: RecordingStatus) {
+    if let Err(e) = app.emit("recording-state", status) {
+        error!("failed to emit recording-state: {:?}", e);
+    }
+}
+
+fn recording_file_path(app: &AppHandle, extension: &str) -> Result<PathBuf, String> {
+    let dir = create_recordings_dir(app)?;
+    let timestamp = unix_millis()?;
+    Ok(dir.join(format!("{RECORDING_FILE_PREFIX}{timestamp}.{extension}")))
+}
+
+fn create_recordings_dir(app: &AppHandle) -> Result<PathBuf, String> {
+    let dir = recordings_dir(app)?;
+    fs::create_dir_all(&dir).map_err(|e| {
+        format!(
+            "Failed to create recordings directory {}: {}",
+            dir.display(),
+            e
+        )
+    })?;
+    Ok(dir)
+}
+
+/// Path for a new recording, named from the configured filename template.
+/// Falls back to `recording_file_path` when no template is set or it can't be
+/// expanded; an existing file gets `_1`, `_2`, ... appended instead of being
+/// overwritten.
+fn new_recording_path(app: &AppHandle, extension: &str) -> Result<PathBuf, String> {
+    let Some(template) = load_recording_config(app)
+        .filename_template
+        .filter(|template| !template.trim().is_empty())
+    else {
+        return recording_file_path(app, extension);
+    };
+    let location = get_saved_location(app.clone()).ok().flatten();
+    let values = filename::TemplateValues {
+        timestamp: unix_millis()?,
+        location: location.as_ref().map(|l| l.location.as_str()),
+        country: location.as_ref().and_then(|l| l.country.as_deref()),
+    };
+    let stem = match filename::expand_template(&template, &values) {
+        Ok(stem) => stem,
+        Err(e) => {
+            warn!("ignoring filename template: {}", e);
+            return recording_file_path(app, extension);
+        }
+    };
+
+    let dir = create_recordings_dir(app)?;
+    let mut path = dir.join(format!("{stem}.{extension}"));
+    let mut suffix = 1;
+    while path.exists() || sidecar_path(&path).exists() {
+        path = dir.join(format!("{stem}_{suffix}.{extension}"));
+        suffix += 1;
+    }
+    Ok(path)
+}
+
+/// Checks that a caller-supplied recording path can be written before the
+/// stream starts, so a bad path fails fast instead of on stop.
+fn validate_output_path(path: &Path) -> Result<(), String> {
+    if path.file_name().is_none() {
+        return Err(format!("Invalid recording path: {}", path.display()));
+    }
+    let parent = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    let metadata = fs::metadata(parent).map_err(|e| {
+        format!(
+            "Recording directory {} is not available: {}",
+            parent.display(),
+            e
+        )
+    })?;
+    if !metadata.is_dir() {
+        return Err(format!("{} is not a directory", parent.display()));
+    }
+    if metadata.permissions().readonly() {
+        return Err(format!(
+            "Recording directory {} is not writable",
+            parent.display()
+        ));
+    }
+    Ok(())
+}
+
+fn create_wav_writer(path: &Path, spec: hound::WavSpec) -> Result<FileWavWriter, String> {
+    hound::WavWriter::create(path, spec)
+        .map_err(|e| format!("Failed to write recording to {}: {}", path.display(), e))
+}
+
+/// Writes full-resolution samples as a 24-bit integer or 32-bit float WAV.
+fn write_hires_wav(
+    path: &Path,
+    samples: &[f32],
+    sample_rate: u32,
+    channels: u16,
+    bit_depth: u16,
+) -> Result<(), String> {
+    let spec = hound::WavSpec {
+        channels,
+        sample_rate,
+        bits_per_sample: bit_depth,
+        sample_format: if bit_depth == 32 {
+            hound::SampleFormat::Float
+        } else {
+            hound::SampleFormat::Int
+        },
+    };
+    let mut writer = create_wav_writer(path, spec)?;
+    let max = ((1i32 << (bit_depth - 1)) - 1) as f32;
+    for &sample in samples {
+        // Float keeps what went over full scale; integers can't.
+        let result = if bit_depth == 32 {
+            writer.write_sample(sample)
+        } else {
+            writer.write_sample((sample.clamp(-1.0, 1.0) * max) as i32)
+        };
+        result.map_err(|e| e.to_string())?;
+    }
+    writer.finalize().map_err(|e| e.to_string())
+}
+
+fn wav_spec(sample_rate: u32, channels: u16) -> hound::WavSpec {
+    hound::WavSpec {
+        channels,
+        sample_rate,
+        bits_per_sample: 16,
+        sample_format: hound::SampleFormat::Int,
+    }
+}
+
+fn device_info(device: &cpal::Device, name: String, id: String) -> Result<DeviceInfo, String> {
+    let default_config = device.default_input_config().map_err(|e| e.to_string())?;
+
+    let mut supported_formats: Vec<String> = Vec::new();
+    if let Ok(configs) = device.supported_input_configs() {
+        for config in configs {
+            let format = config.sample_format().to_string();
+            if !supported_formats.contains(&format) {
+                supported_formats.push(format);
+            }
+        }
+    }
+
+    Ok(DeviceInfo {
+        id,
+        name,
+        default_sample_rate: default_config.sample_rate().0,
+        channels: default_config.channels(),
+        supported_formats,
+    })
+}
+
+/// Identifier for an input device that tells apart devices with the same name.
+///
+/// cpal exposes no persistent endpoint ID (the WASAPI endpoint ID or the Core
+/// Audio UID), so the ID is the device name, with `#2`, `#3`, ... appended to
+/// the second and later devices sharing it in enumeration order. On ALSA the
+/// names already carry the card and are unique. Elsewhere, identical devices
+/// keep their IDs across reboots as long as the system enumerates them in the
+/// same order; devices with unique names degrade to plain name matching.
+fn input_device_id(name: &str, ordinal: usize) -> String {
+    if ordinal == 0 {
+        name.to_string()
+    } else {
+        format!("{}#{}", name, ordinal + 1)
+    }
+}
+
+/// Input devices paired with their names and `input_device_id`s, skipping
+/// those whose name can't be read.
+fn input_devices_with_ids(
+    host: &cpal::Host,
+) -> Result<Vec<(cpal::Device, String, String)>, String> {
+    let mut seen: BTreeMap<String, usize> = BTreeMap::new();
+    let mut devices = Vec::new();
+    for device in host.input_devices().map_err(|e| e.to_string())? {
+        let name = match device.name() {
+            Ok(name) => name,
+            Err(e) => {
+                error!("skipping input device: {}", e);
+                continue;
+            }
+        };
+        let ordinal = seen.entry(name.clone()).or_insert(0);
+        let id = input_device_id(&name, *ordinal);
+        *ordinal += 1;
+        devices.push((device, name, id));
+    }
+    Ok(devices)
+}
+
+/// Looks a microphone up by `input_device_id` first, then by name. An ID that
+/// matches nothing, e.g. because one of two identical devices was unplugged,
+/// falls back to `device_name` rather than guessing.
+fn find_input_device_by_id(
+    host: &cpal::Host,
+    device_id: Option<&str>,
+    device_name: Option<&str>,
+) -> Result<cpal::Device, String> {
+    if let Some(wanted) = device_id {
+        let found = input_devices_with_ids(host)?
+            .into_iter()
+            .find(|(_, _, id)| id == wanted);
+        if let Some((device, ..)) = found {
+            return Ok(device);
+        }
+        if device_name.is_none() {
+            return Err(format!("Input device not found: {wanted}"));
+        }
+        warn!(device_id = %wanted, "input device id not found; matching by name");
+    }
+    find_input_device(host, device_name)
+}
+
+fn find_input_device(host: &cpal::Host, device_name: Option<&str>) -> Result<cpal::Device, String> {
+    match device_name {
+        Some(wanted) => host
+            .input_devices()
+            .map_err(|e| e.to_string())?
+            .find(|device| device.name().map(|name| name == wanted).unwrap_or(false))
+            .ok_or_else(|| format!("Input device not found: {wanted}")),
+        None => host
+            .default_input_device()
+            .ok_or_else(|| "No input device available".to_string()),
+    }
+}
+
+/// Output device to capture system audio from. cpal records an output device
+/// in loopback mode when an input stream is built on it, which only WASAPI
+/// supports.
+#[cfg(target_os = "windows")]
+fn find_loopback_device(
+    host: &cpal::Host,
+    device_name: Option<&str>,
+) -> Result<cpal::Device, String> {
+    match device_name {
+        Some(wanted) => host
+            .output_devices()
+            .map_err(|e| e.to_string())?
+            .find(|device| device.name().map(|name| name == wanted).unwrap_or(false))
+            .ok_or_else(|| format!("Output device not found: {wanted}")),
+        None => host
+            .default_output_device()
+            .ok_or_else(|| "No output device available".to_string()),
+    }
+}
+
+#[cfg(not(target_os = "windows"))]
+fn find_loopback_device(
+    _host: &cpal::Host,
+    _device_name: Option<&str>,
+) -> Result<cpal::Device, String> {
+    Err(LOOPBACK_UNSUPPORTED.to_string())
+}
+
+#[cfg(not(target_os = "windows"))]
+const LOOPBACK_UNSUPPORTED: &str = "System audio capture is not supported on this platform";
+
+/// Picks an input config with the requested format and rate, staying as
+/// close to the default config as the device allows: same channel count if
+/// possible, and the default format and rate for whichever was not requested.
+fn select_input_config(
+    device: &cpal::Device,
+    format: Option<SampleFormat>,
+    sample_rate: Option<u32>,
+) -> Result<cpal::SupportedStreamConfig, String> {
+    let default_config = device.default_input_config().map_err(|e| e.to_string())?;
+    let format = format.unwrap_or(default_config.sample_format());
+    let rate = sample_rate.map_or(default_config.sample_rate(), cpal::SampleRate);
+    if default_config.sample_format() == format && default_config.sample_rate() == rate {
+        return Ok(default_config);
+    }
+
+    let supported: Vec<_> = device
+        .supported_input_configs()
+        .map_err(|e| e.to_string())?
+        .collect();
+    let ranges: Vec<_> = supported
+        .iter()
+        .filter(|range| range.sample_format() == format)
+        .filter(|range| {
+            sample_rate.is_none()
+                || (range.min_sample_rate() <= rate && rate <= range.max_sample_rate())
+        })
+        .collect();
+    let Some(&&range) = ranges
+        .iter()
+        .find(|range| range.channels() == default_config.channels())
+        .or_else(|| ranges.first())
+    else {
+        let listed: Vec<String> = supported
+            .iter()
+            .map(|range| {
+                format!(
+                    "{} {}-{} Hz ({} ch)",
+                    range.sample_format(),
+                    range.min_sample_rate().0,
+                    range.max_sample_rate().0,
+                    range.channels()
+                )
+            })
+            .collect();
+        return Err(format!(
+            "Input device does not support {} at {} Hz; supported: {}",
+            format,
+            rate.0,
+            listed.join(", ")
+        ));
+    };
+    let rate = rate.clamp(range.min_sample_rate(), range.max_sample_rate());
+    Ok(range.with_sample_rate(rate))
+}
+
+/// Names of the output devices whose audio can be recorded with the
+/// `system_loopback` source.
+#[tauri::command]
+fn list_loopback_devices() -> Result<Vec<String>, String> {
+    #[cfg(target_os = "windows")]
+    {
+        let host = cpal::default_host();
+        let devices = host.output_devices().map_err(|e| e.to_string())?;
+        Ok(devices.filter_map(|device| device.name().ok()).collect())
+    }
+    #[cfg(not(target_os = "windows"))]
+    {
+        Err(LOOPBACK_UNSUPPORTED.to_string())
+    }
+}
+
+#[tauri::command]
+fn list_input_devices() -> Result<Vec<DeviceInfo>, String> {
+    let host = cpal::default_host();
+    let devices = input_devices_with_ids(&host)?;
+
+    let mut infos = Vec::new();
+    for (device, name, id) in devices {
+        match device_info(&device, name, id) {
+            Ok(info) => infos.push(info),
+            Err(e) => error!("skipping input device: {}", e),
+        }
+    }
+    Ok(infos)
+}
+
+/// Re-enumerates the input devices and emits `device-changed` if the one
+/// used last is no longer among them.
+#[tauri::command]
+fn refresh_devices(app: AppHandle) -> Result<Vec<DeviceInfo>, String> {
+    let devices = list_input_devices()?;
+    let previous = load_recording_config(&app).last_device_name;
+    if let Some(previous) = previous {
+        if !devices.iter().any(|device| device.name == previous) {
+            warn!(device = %previous, "Previously used input device is gone");
+            emit_device_changed(&app, previous, None);
+        }
+    }
+    Ok(devices)
+}
+
+fn emit_device_changed(app: &AppHandle, previous: String, current: Option<String>) {
+    if let Err(e) = app.emit("device-changed", DeviceChanged { previous, current }) {
+        error!("failed to emit device-changed: {:?}", e);
+    }
+}
+
+/// Reports which microphone a recording is using and remembers it, so a
+/// swapped or unplugged device doesn't go unnoticed.
+fn note_selected_device(app: &AppHandle, host: &cpal::Host, name: &str) {
+    let mut config = load_recording_config(app);
+    let previous = config.last_device_name.clone();
+    let changed = previous.as_deref() != Some(name);
+    if changed {
+        info!(device = %name, previous = ?previous, "Input device changed");
+        if let Some(previous) = &previous {
+            let still_present = host
+                .input_devices()
+                .map(|mut devices| {
+                    devices.any(|device| device.name().is_ok_and(|n| &n == previous))
+                })
+                .unwrap_or(false);
+            if !still_present {
+                emit_device_changed(app, previous.clone(), Some(name.to_string()));
+            }
+        }
+    }
+    let selected = DeviceSelected {
+        name: name.to_string(),
+        previous,
+        changed,
+    };
+    if let Err(e) = app.emit("device-selected", selected) {
+        error!("failed to emit device-selected: {:?}", e);
+    }
+
+    if changed {
+        config.last_device_name = Some(name.to_string());
+        if let Err(e) = save_recording_config(app, &config) {
+            warn!("failed to remember input device: {}", e);
+        }
+    }
+}
+
+/// WAV bit depths `start_recording` can produce from the given input device,
+/// or the default one. Anything above 16 bits needs a device that supports
+/// f32 capture (see `preferred_format`).
+#[tauri::command]
+fn get_supported_bit_depths(device_name: Option<String>) -> Result<Vec<u16>, String> {
+    let host = cpal::default_host();
+    let device = find_input_device(&host, device_name.as_deref())?;
+    let has_float = device
+        .supported_input_configs()
+        .map_err(|e| e.to_string())?
+        .any(|config| config.sample_format() == SampleFormat::F32);
+    Ok(if has_float {
+        SUPPORTED_BIT_DEPTHS.to_vec()
+    } else {
+        vec![16]
+    })
+}
+
+#[tauri::command]
+fn get_input_device_config() -> Result<DeviceConfigInfo, String> {
+    let host = cpal::default_host();
+    let device = find_input_device(&host, None)?;
+    let name = device.name().map_err(|e| e.to_string())?;
+    let default_config = device.default_input_config().map_err(|e| e.to_string())?;
+    let supported_configs = device
+        .supported_input_configs()
+        .map_err(|e| e.to_string())?
+        .map(|config| SupportedConfigInfo {
+            min_sample_rate: config.min_sample_rate().0,
+            max_sample_rate: config.max_sample_rate().0,
+            channels: config.channels(),
+            sample_format: config.sample_format().to_string(),
+        })
+        .collect();
+
+    Ok(DeviceConfigInfo {
+        name,
+        default_sample_rate: default_config.sample_rate().0,
+        default_channels: default_config.channels(),
+        supported_configs,
+    })
+}
+
+// Every option is a separate optional argument so `invoke("start_recording")`
+// keeps working with no payload. `preserve_float` is an alias for
+// `bit_depth: 32`, a float WAV; an explicit `bit_depth` wins.
+#[allow(clippy::too_many_arguments)]
+#[tauri::command]
+#[tracing::instrument(skip_all)]
+fn start_recording(
+    state: State<RecordingState>,
+    app: AppHandle,
+    device_name: Option<String>,
+    stream_to_disk: Option<bool>,
+    max_duration_secs: Option<u32>,
+    channel_mode: Option<ChannelMode>,
+    output_path: Option<String>,
+    vad: Option<VadSettings>,
+    segment_secs: Option<u32>,
+    source: Option<CaptureSource>,
+    preferred_format: Option<InputSampleFormat>,
+    sample_rate: Option<u32>,
+    bit_depth: Option<u16>,
+    channel_select: Option<usize>,
+    buffer_frames: Option<u32>,
+    pcm_events: Option<bool>,
+    device_id: Option<String>,
+    preserve_float: Option<bool>,
+) -> Result<(), String> {
+    info!("Starting recording");
+    if segment_secs == Some(0) {
+        return Err("Segment length must be greater than zero".to_string());
+    }
+    if buffer_frames == Some(0) {
+        return Err("Buffer size must be greater than zero".to_string());
+    }
+    if sample_rate == Some(0) {
+        return Err("Sample rate must be greater than zero".to_string());
+    }
+    let bit_depth = bit_depth.or(preserve_float.unwrap_or(false).then_some(32));
+    let defaults = load_recording_config(&app).start_options();
+    let source = source.unwrap_or_default();
+    let streams = match source {
+        CaptureSource::Mixed => stream_to_disk.unwrap_or(false),
+        _ => stream_to_disk.unwrap_or(defaults.stream_to_disk),
+    };
+    // A preset's capture format is only a fit for a plain in-memory microphone
+    // recording: system audio comes in the output device's format, and mixed
+    // or streamed recordings are 16-bit.
+    let (preset_rate, preset_depth) =
+        if source == CaptureSource::Microphone && !streams && segment_secs.is_none() {
+            (defaults.sample_rate, defaults.bit_depth)
+        } else {
+            (None, None)
+        };
+    let options = StartOptions {
+        source,
+        // The saved device is a microphone; don't try to loop it back.
+        device_name: match source {
+            CaptureSource::Microphone | CaptureSource::Mixed => {
+                device_name.or(defaults.device_name)
+            }
+            CaptureSource::SystemLoopback => device_name,
+        },
+        device_id: match source {
+            CaptureSource::Microphone | CaptureSource::Mixed => device_id,
+            CaptureSource::SystemLoopback => None,
+        },
+        // Mixing happens on stop, so a saved streaming preference is ignored.
+        stream_to_disk: streams,
+        max_duration_secs,
+        channel_mode: channel_mode.unwrap_or(defaults.channel_mode),
+        output_path: output_path.map(PathBuf::from),
+        vad,
+        segment_secs,
+        preferred_format,
+        sample_rate: sample_rate.or(preset_rate),
+        bit_depth: bit_depth.or(preset_depth),
+        channel_select,
+        downmix_mode: defaults.downmix_mode,
+        buffer_frames,
+        pcm_events: pcm_events.unwrap_or(false),
+        start_delay: None,
+    };
+    let result = start_capture(&state, &app, options);
+    if result.is_err() {
+        // Starting closed the monitor; bring pre-roll back.
+        resume_pre_roll(&state, &app);
+    }
+    result
+}
+
+/// Longest warm-up `start_recording_with_delay` accepts.
+const MAX_START_DELAY_MS: u32 = 10_000;
+/// Interval between `countdown` events.
+const COUNTDOWN_TICK: Duration = Duration::from_millis(100);
+
+/// Payload of `countdown`.
+#[derive(serde::Serialize, Clone)]
+struct Countdown {
+    /// Time left before recording begins; the last event carries 0.
+    remaining_ms: u64,
+}
+
+fn check_start_delay(delay_ms: u32) -> Result<(), String> {
+    if delay_ms > MAX_START_DELAY_MS {
+        return Err(format!(
+            "Start delay must be at most {} ms, got {}",
+            MAX_START_DELAY_MS, delay_ms
+        ));
+    }
+    Ok(())
+}
+
+/// Opens the microphone with the saved settings and lets it run for
+/// `delay_ms` (the saved `start_delay_ms` when omitted, otherwise none)
+/// before recording, so a device that is slow to start doesn't swallow the
+/// first words. `countdown` events are emitted every 100 ms until recording
+/// begins.
+#[tauri::command]
+fn start_recording_with_delay(
+    state: State<RecordingState>,
+    app: AppHandle,
+    delay_ms: Option<u32>,
+) -> Result<(), String> {
+    let config = load_recording_config(&app);
+    let delay_ms = delay_ms.or(config.start_delay_ms).unwrap_or(0);
+    check_start_delay(delay_ms)?;
+    info!(delay_ms, "Starting recording after a delay");
+    let options = StartOptions {
+        start_delay: Some(Duration::from_millis(delay_ms as u64)).filter(|d| !d.is_zero()),
+        ..config.start_options()
+    };
+    let result = start_capture(&state, &app, options);
+    if result.is_err() {
+        resume_pre_roll(&state, &app);
+    }
+    result
+}
+
+/// Emits `countdown` until the warm-up ending at `until` is over, or the
+/// recording is stopped or restarted first.
+fn spawn_countdown(state: &RecordingState, app: &AppHandle, until: Instant) {
+    let state = state.clone();
+    let app = app.clone();
+    std::thread::spawn(move || loop {
+        // The deadline is cleared once the warm-up ends; before that, a
+        // different one means another recording has taken over.
+        let replaced =
+            *state.warmup_until.lock_or_recover() != Some(until) && Instant::now() < until;
+        if replaced || state.stream.lock_or_recover().is_none() {
+            return;
+        }
+        let remaining = until.saturating_duration_since(Instant::now());
+        let _ = app.emit(
+            "countdown",
+            Countdown {
+                remaining_ms: remaining.as_millis() as u64,
+            },
+        );
+        if remaining.is_zero() {
+            return;
+        }
+        std::thread::sleep(remaining.min(COUNTDOWN_TICK));
+    });
+}
+
+/// Input device chosen for a stream, with the config it will be opened with.
+struct OpenedInput {
+    device: cpal::Device,
+    name: String,
+    sample_format: SampleFormat,
+    config: cpal::StreamConfig,
+}
+
+/// Device and config selection shared by recording and monitoring.
+fn open_input_device(
+    host: &cpal::Host,
+    app: &AppHandle,
+    options: &StartOptions,
+) -> Result<OpenedInput, String> {
+    let (device, supported_config) = match options.source {
+        CaptureSource::Microphone | CaptureSource::Mixed => {
+            permission::ensure_microphone_access(app)?;
+            let device = find_input_device_by_id(
+                host,
+                options.device_id.as_deref(),
+                options.device_name.as_deref(),
+            )?;
+            let config = select_input_config(
+                &device,
+                options.preferred_format.map(InputSampleFormat::to_cpal),
+                options.sample_rate,
+            )?;
+            (device, config)
+        }
+        CaptureSource::SystemLoopback => {
+            if options.preferred_format.is_some() || options.sample_rate.is_some() {
+                return Err(
+                    "System audio is always captured in the output device's format".to_string(),
+                );
+            }
+            let device = find_loopback_device(host, options.device_name.as_deref())?;
+            // Loopback streams run at the output device's mix format.
+            let config = device.default_output_config().map_err(|e| e.to_string())?;
+            (device, config)
+        }
+    };
+
+    let name = device.name().unwrap_or_default();
+    info!(device = %name, "Using input device");
+
+    let sample_format = supported_config.sample_format();
+    let buffer_range = *supported_config.buffer_size();
+    let mut config: cpal::StreamConfig = supported_config.into();
+    if config.channels == 0 {
+        return Err("device reported zero channels".to_string());
+    }
+    if config.sample_rate.0 == 0 {
+        return Err("device reported a zero sample rate".to_string());
+    }
+    if let Some(frames) = options.buffer_frames {
+        match buffer_range {
+            cpal::SupportedBufferSize::Range { min, max } if !(min..=max).contains(&frames) => {
+                let reason = format!("the device supports {} to {} frames", min, max);
+                emit_buffer_size_fallback(app, frames, reason);
+            }
+            _ => config.buffer_size = cpal::BufferSize::Fixed(frames),
+        }
+    }
+    Ok(OpenedInput {
+        device,
+        name,
+        sample_format,
+        config,
+    })
+}
+
+/// Payload of `buffer-size-fallback`: the requested fixed buffer size was
+/// rejected and the stream runs with the driver's default instead.
+#[derive(serde::Serialize, Clone)]
+struct BufferSizeFallback {
+    requested_frames: u32,
+    reason: String,
+}
+
+fn emit_buffer_size_fallback(app: &AppHandle, requested_frames: u32, reason: String) {
+    warn!(
+        "buffer size of {} frames rejected ({}); using the default",
+        requested_frames, reason
+    );
+    let payload = BufferSizeFallback {
+        requested_frames,
+        reason,
+    };
+    if let Err(e) = app.emit("buffer-size-fallback", payload) {
+        error!("failed to emit buffer-size-fallback: {:?}", e);
+    }
+}
+
+/// Builds the input stream, retrying with the default buffer size when the
+/// device refuses the fixed one that was requested.
+fn build_input_stream_or_default(
+    input: &mut OpenedInput,
+    capture: CaptureSettings,
+    state: &RecordingState,
+    app: &AppHandle,
+) -> Result<cpal::Stream, String> {
+    match (
+        build_input_stream(input, capture, state, app),
+        input.config.buffer_size,
+    ) {
+        (Err(e), cpal::BufferSize::Fixed(frames)) => {
+            emit_buffer_size_fallback(app, frames, e);
+            input.config.buffer_size = cpal::BufferSize::Default;
+            reset_stream_info(state, &input.config, input.sample_format);
+            build_input_stream(input, capture, state, app)
+        }
+        (result, _) => result,
+    }
+}
+
+fn reset_stream_info(state: &RecordingState, config: &cpal::StreamConfig, format: SampleFormat) {
+    let fixed_buffer = match config.buffer_size {
+        cpal::BufferSize::Fixed(frames) => Some(frames),
+        cpal::BufferSize::Default => None,
+    };
+    *state.stream_info.lock_or_recover() = StreamInfo {
+        sample_rate: config.sample_rate.0,
+        channels: config.channels,
+        sample_format: format.to_string(),
+        buffer_frames: fixed_buffer,
+        fixed_buffer: fixed_buffer.is_some(),
+        ..StreamInfo::default()
+    };
+}
+
+/// Builds the (unstarted) input stream feeding `process_input`.
+fn build_input_stream(
+    input: &OpenedInput,
+    capture: CaptureSettings,
+    state: &RecordingState,
+    app: &AppHandle,
+) -> Result<cpal::Stream, String> {
+    let recording = state.clone();
+    let device = &input.device;
+    let config = &input.config;
+
+    match input.sample_format {
+        SampleFormat::F32 => {
+            let on_error = stream_error_handler(&recording, app);
+            let recording = recording.clone();
+            let app = app.clone();
+            device.build_input_stream(
+                config,
+                move |data: &[f32], _: &_| {
+                    process_input_f32(data, &capture, &recording, &app);
+                },
+                on_error,
+                None,
+            )
+        }
+        SampleFormat::I16 => {
+            let on_error = stream_error_handler(&recording, app);
+            let recording = recording.clone();
+            let app = app.clone();
+            device.build_input_stream(
+                config,
+                move |data: &[i16], _: &_| {
+                    process_input_i16(data, &capture, &recording, &app);
+                },
+                on_error,
+                None,
+            )
+        }
+        SampleFormat::U16 => {
+            let on_error = stream_error_handler(&recording, app);
+            let recording = recording.clone();
+            let app = app.clone();
+            device.build_input_stream(
+                config,
+                move |data: &[u16], _: &_| {
+                    process_input_u16(data, &capture, &recording, &app);
+                },
+                on_error,
+                None,
+            )
+        }
+        _ => return Err("Unsupported sample format".to_string()),
+    }
+    .map_err(|e| e.to_string())
+}
+
+/// Opens the microphone and emits `audio-level` and `waveform-frame` without
+/// keeping any audio, so levels can be checked before recording. Starting a
+/// recording stops the monitor.
+#[tauri::command]
+fn start_monitoring(
+    state: State<RecordingState>,
+    app: AppHandle,
+    device_name: Option<String>,
+    channel_mode: Option<ChannelMode>,
+    preferred_format: Option<InputSampleFormat>,
+    sample_rate: Option<u32>,
+) -> Result<(), String> {
+    if state.stream.lock_or_recover().is_some() {
+        return Err("Cannot monitor while recording".to_string());
+    }
+    if state.mic_testing.load(Ordering::SeqCst) {
+        return Err("Cannot monitor during a mic test".to_string());
+    }
+    let mut monitor_guard = state.monitor_stream.lock_or_recover();
+    if monitor_guard.is_some() {
+        return Ok(());
+    }
+
+    let defaults = load_recording_config(&app).start_options();
+    let options = StartOptions {
+        device_name: device_name.or(defaults.device_name),
+        channel_mode: channel_mode.unwrap_or(defaults.channel_mode),
+        preferred_format,
+        sample_rate,
+        downmix_mode: defaults.downmix_mode,
+        ..StartOptions::default()
+    };
+    *monitor_guard = Some(open_monitor(&state, &app, &options)?);
+    emit_mic_live(&app, true);
+    info!("Monitoring started");
+    Ok(())
+}
+
+fn emit_mic_live(app: &AppHandle, live: bool) {
+    if let Err(e) = app.emit("mic-live", MicLive { live }) {
+        error!("failed to emit mic-live: {:?}", e);
+    }
+}
+
+/// Drops the monitor stream, if any, releasing the microphone.
+fn close_monitor(state: &RecordingState, app: &AppHandle) {
+    if state.monitor_stream.lock_or_recover().take().is_some() {
+        emit_mic_live(app, false);
+    }
+}
+
+/// Opens and starts a meter-only stream, which also fills the pre-roll buffer
+/// when `pre_roll_ms` is configured. Shared by `start_monitoring` and
+/// `resume_pre_roll`.
+fn open_monitor(
+    state: &RecordingState,
+    app: &AppHandle,
+    options: &StartOptions,
+) -> Result<CpalStreamWrapper, String> {
+    let host = cpal::default_host();
+    let input = open_input_device(&host, app, options)?;
+    let pre_roll_ms = load_recording_config(app).pre_roll_ms.unwrap_or(0);
+    let output_channels = options.channel_mode.output_channels();
+    let capture = CaptureSettings {
+        monitor_only: true,
+        channel_select: options.selected_channel(input.config.channels),
+        pre_roll_samples: (pre_roll_ms as u64 * input.config.sample_rate.0 as u64 / 1000) as usize
+            * output_channels as usize,
+        ..CaptureSettings::for_stream(&input.config, options.channel_mode)
+    };
+    reset_capture_state(state, &capture, input.name.clone(), None);
+    reset_stream_info(state, &input.config, input.sample_format);
+    *state.pre_roll.lock_or_recover() = PreRoll {
+        samples: VecDeque::with_capacity(capture.pre_roll_samples),
+        device_name: input.name.clone(),
+        sample_rate: capture.sample_rate,
+        channels: output_channels,
+        channel_select: capture.channel_select,
+    };
+
+    let stream = build_input_stream(&input, capture, state, app)?;
+    stream.play().map_err(|e| e.to_string())?;
+    Ok(CpalStreamWrapper(stream))
+}
+
+/// Reopens the monitor stream that feeds the pre-roll buffer, if pre-roll is
+/// configured and armed and no recording, monitor or mic test is running.
+fn resume_pre_roll(state: &RecordingState, app: &AppHandle) {
+    let config = load_recording_config(app);
+    if config.pre_roll_ms.unwrap_or(0) == 0
+        || !state.pre_roll_armed.load(Ordering::Relaxed)
+        || state.mic_testing.load(Ordering::SeqCst)
+        || state.stream.lock_or_recover().is_some()
+    {
+        return;
+    }
+    let mut monitor_guard = state.monitor_stream.lock_or_recover();
+    if monitor_guard.is_some() {
+        return;
+    }
+    match open_monitor(state, app, &config.start_options()) {
+        Ok(stream) => {
+            *monitor_guard = Some(stream);
+            emit_mic_live(app, true);
+        }
+        Err(e) => warn!("pre-roll is unavailable: {}", e),
+    }
+}
+
+#[tauri::command]
+fn get_pre_roll(app: AppHandle) -> u32 {
+    load_recording_config(&app).pre_roll_ms.unwrap_or(0)
+}
+
+#[tauri::command]
+fn get_quality_preset(app: AppHandle) -> Option<QualityPreset> {
+    load_recording_config(&app).quality_preset
+}
+
+/// Saves the preset later recordings start from; `None` goes back to the
+/// individual settings alone.
+#[tauri::command]
+fn set_quality_preset(app: AppHandle, preset: Option<QualityPreset>) -> Result<(), String> {
+    let mut config = load_recording_config(&app);
+    config.quality_preset = preset;
+    save_recording_config(&app, &config)?;
+    info!(?preset, "Quality preset set");
+    Ok(())
+}
+
+/// Saves the pre-roll length and, unless a recording is running, restarts the
+/// monitor stream with it if pre-roll is armed. Zero turns pre-roll off and
+/// closes the monitor.
+#[tauri::command]
+fn set_pre_roll(state: State<RecordingState>, app: AppHandle, ms: u32) -> Result<(), String> {
+    if ms > MAX_PRE_ROLL_MS {
+        return Err(format!(
+            "Pre-roll of {} ms is longer than the {} ms maximum",
+            ms, MAX_PRE_ROLL_MS
+        ));
+    }
+    let mut config = load_recording_config(&app);
+    config.pre_roll_ms = (ms > 0).then_some(ms);
+    save_recording_config(&app, &config)?;
+    if state.stream.lock_or_recover().is_none() {
+        close_monitor(&state, &app);
+        resume_pre_roll(&state, &app);
+    }
+    Ok(())
+}
+
+/// Arms or disarms pre-roll for this session. While armed and `pre_roll_ms`
+/// is set, the microphone stays open between recordings; disarming closes
+/// it. Not saved, so the app never opens the microphone on launch.
+#[tauri::command]
+fn set_pre_roll_armed(state: State<RecordingState>, app: AppHandle, armed: bool) {
+    info!(armed, "Setting pre-roll armed");
+    state.pre_roll_armed.store(armed, Ordering::Relaxed);
+    if state.stream.lock_or_recover().is_none() {
+        close_monitor(&state, &app);
+        resume_pre_roll(&state, &app);
+    }
+}
+
+#[tauri::command]
+fn stop_monitoring(state: State<RecordingState>, app: AppHandle) -> Result<(), String> {
+    close_monitor(&state, &app);
+    Ok(())
+}
+
+/// Builds and starts the input stream. Shared by `start_recording` and the
+/// push-to-talk shortcut.
+fn start_capture(
+    state: &RecordingState,
+    app: &AppHandle,
+    options: StartOptions,
+) -> Result<(), String> {
+    let Some((mut stream_guard, bit_depth)) = begin_capture(state, &options)? else {
+        info!("Recording already running");
+        return Ok(());
+    };
+
+    // The monitor would hold the same device open. Once it is closed the
+    // pre-roll buffer no longer changes.
+    close_monitor(state, app);
+    let pre_roll = std::mem::take(&mut *state.pre_roll.lock_or_recover());
+
+    let host = cpal::default_host();
+    let mut input = open_input_device(&host, app, &options)?;
+    if options.source != CaptureSource::SystemLoopback {
+        // Monitor and pre-roll streams don't count as selecting a device.
+        note_selected_device(app, &host, &input.name);
+        apply_device_settings(state, app, options.device_id.as_deref(), &input.name);
+    }
+    let config = &input.config;
+    // A 16-bit device has nothing to add beyond a 16-bit file.
+    if bit_depth > 16 && input.sample_format != SampleFormat::F32 {
+        return Err(format!(
+            "{}-bit recording needs an f32 input, but the device delivers {}",
+            bit_depth, input.sample_format
+        ));
+    }
+    if let Some(index) = options.channel_select {
+        if index >= config.channels as usize {
+            return Err(format!(
+                "Channel {} does not exist; the device has {} input channels (counting from 0)",
+                index, config.channels
+            ));
+        }
+        if options.channel_mode == ChannelMode::Stereo {
+            return Err("A selected channel can only be recorded as mono".to_string());
+        }
+    }
+    let output_channels = options.channel_mode.output_channels();
+    let capture = CaptureSettings {
+        max_samples: options
+            .max_duration_secs
+            .map(|secs| secs as u64 * config.sample_rate.0 as u64 * output_channels as u64),
+        vad: options.vad.map(|vad| VadGate {
+            onset: audio::dbfs_to_linear(vad.onset_dbfs),
+            hangover_frames: vad.hangover_ms * config.sample_rate.0 as u64 / 1000,
+        }),
+        segment_samples: options
+            .segment_secs
+            .map(|secs| secs as u64 * config.sample_rate.0 as u64 * output_channels as u64),
+        bit_depth,
+        channel_select: options.selected_channel(config.channels),
+        pcm_events: options.pcm_events,
+        ..CaptureSettings::for_stream(config, options.channel_mode)
+    };
+
+    reset_capture_state(
+        state,
+        &capture,
+        input.name.clone(),
+        options.output_path.clone(),
+    );
+    reset_stream_info(state, config, input.sample_format);
+    if options.stream_to_disk || options.segment_secs.is_some() {
+        let mut file_path = match &options.output_path {
+            Some(path) => path.clone(),
+            None => new_recording_path(app, OutputFormat::Wav.extension())?,
+        };
+        if options.segment_secs.is_some() {
+            *state.segment_base.lock_or_recover() = Some(file_path.clone());
+            file_path = segment_path(&file_path, 0);
+        }
+        let writer =
+            create_wav_writer(&file_path, wav_spec(config.sample_rate.0, output_channels))?;
+        *state.writer.lock_or_recover() = Some(writer);
+        *state.output_path.lock_or_recover() = Some(file_path);
+    }
+
+    // Pre-roll only fits in front when it was captured the same way. Mixed
+    // recordings would knock the microphone out of step with the loopback.
+    let pre_roll_fits = pre_roll.device_name == input.name
+        && pre_roll.sample_rate == capture.sample_rate
+        && pre_roll.channels == output_channels
+        && pre_roll.channel_select == capture.channel_select
+        && bit_depth == 16
+        && options.source == CaptureSource::Microphone
+        && options.start_delay.is_none();
+    if pre_roll_fits && !pre_roll.samples.is_empty() {
+        let samples: Vec<i16> = pre_roll.samples.into();
+        state
+            .captured_samples
+            .fetch_add(samples.len() as u64, Ordering::Relaxed);
+        store_samples(state, &capture, app, samples);
+    }
+
+    let stream = build_input_stream_or_default(&mut input, capture, state, app)?;
+
+    let loopback = match options.source {
+        CaptureSource::Mixed => Some(open_loopback_stream(
+            &host,
+            state,
+            app,
+            options.channel_mode,
+        )?),
+        _ => None,
+    };
+
+    let warmup_until = options.start_delay.map(|delay| Instant::now() + delay);
+    *state.warmup_until.lock_or_recover() = warmup_until;
+    stream.play().map_err(|e| e.to_string())?;
+    if let Some(loopback) = loopback {
+        loopback.play().map_err(|e| e.to_string())?;
+        *state.loopback_stream.lock_or_recover() = Some(CpalStreamWrapper(loopback));
+    }
+
+    *stream_guard = Some(CpalStreamWrapper(stream));
+    // Only a recording that actually started replaces an undoable cancel.
+    discard_cancelled_recording(&state.cancelled);
+    *state.started_at.lock_or_recover() = Some(Instant::now());
+    if state.writer.lock_or_recover().is_none() {
+        match recovery::open_recovery_file(app, capture.sample_rate, output_channels) {
+            Ok(file) => *state.recovery.lock_or_recover() = Some(file),
+            Err(e) => warn!("recording without a recovery file: {}", e),
+        }
+    }
+    recovery::spawn_checkpointer(state);
+    let active = ActiveRecordingConfig {
+        sample_rate: capture.sample_rate,
+        channels: output_channels,
+        sample_format: input.sample_format.to_string(),
+        device_name: input.name.clone(),
+    };
+    if let Err(e) = app.emit("recording-config", active) {
+        error!("failed to emit recording-config: {:?}", e);
+    }
+    emit_recording_state(app, recording_status(state, "recording"));
+    if let Some(until) = warmup_until {
+        spawn_countdown(state, app, until);
+    }
+    info!("Recording started successfully");
+    Ok(())
+}
+
+/// The part of `start_capture` before any device is touched: takes the stream
+/// lock, checks the options and resolves the bit depth. Returns `None`, and
+/// holds nothing, when a recording is already running.
+fn begin_capture<'a>(
+    state: &'a RecordingState,
+    options: &StartOptions,
+) -> Result<Option<(StreamGuard<'a>, u16)>, String> {
+    let stream_guard = state.stream.lock_or_recover();
+    if stream_guard.is_some() {
+        return Ok(None);
+    }
+    if state.mic_testing.load(Ordering::SeqCst) {
+        return Err("A mic test is running".to_string());
+    }
+    if let Some(path) = &options.output_path {
+        validate_output_path(path)?;
+    }
+    if options.source == CaptureSource::Mixed {
+        if options.stream_to_disk || options.segment_secs.is_some() {
+            return Err("Mixed recordings cannot be streamed to disk or segmented".to_string());
+        }
+        // Dropping microphone frames would shift it against the system audio.
+        if options.vad.is_some() {
+            return Err("Voice activation is not supported for mixed recordings".to_string());
+        }
+    }
+    let bit_depth = options.bit_depth.unwrap_or(16);
+    if !SUPPORTED_BIT_DEPTHS.contains(&bit_depth) {
+        return Err(format!(
+            "Unsupported bit depth {}; expected one of {:?}",
+            bit_depth, SUPPORTED_BIT_DEPTHS
+        ));
+    }
+    if bit_depth > 16
+        && (options.stream_to_disk
+            || options.segment_secs.is_some()
+            || options.source == CaptureSource::Mixed)
+    {
+        return Err(format!(
+            "{}-bit recordings are kept in memory and cannot be streamed, segmented or mixed",
+            bit_depth
+        ));
+    }
+    Ok(Some((stream_guard, bit_depth)))
+}
+
+/// Whether a recording is running, so a reloaded UI can re-sync.
+#[tauri::command]
+fn is_recording(state: State<RecordingState>) -> bool {
+    state.stream.lock_or_recover().is_some()
+}
+
+/// Parameters of the running input stream and how often its callback fires.
+#[tauri::command]
+fn get_stream_info(state: State<RecordingState>) -> Result<StreamInfo, String> {
+    if state.stream.lock_or_recover().is_none() {
+        return Err("Recording is not running".to_string());
+    }
+    Ok(state.stream_info.lock_or_recover().clone())
+}
+
+/// Length of the audio captured so far in milliseconds, derived from the
+/// sample count rather than wall-clock time so pauses are not counted.
+#[tauri::command]
+fn get_recording_duration(state: State<RecordingState>) -> Result<u64, String> {
+    if state.stream.lock_or_recover().is_none() {
+        return Ok(0);
+    }
+    let sample_rate = *state.sample_rate.lock_or_recover();
+    let channels = *state.output_channels.lock_or_recover();
+    let sample_count = state.captured_samples.load(Ordering::Relaxed) as usize;
+    Ok(duration_ms(sample_count, sample_rate, channels))
+}
+
+// Flat optional arguments, like `start_recording`.
+#[allow(clippy::too_many_arguments)]
+#[tauri::command]
+#[tracing::instrument(skip_all)]
+fn stop_recording(
+    state: State<RecordingState>,
+    app: AppHandle,
+    format: Option<OutputFormat>,
+    target_sample_rate: Option<u32>,
+    normalize_peak_dbfs: Option<f32>,
+    allow_empty: Option<bool>,
+    denoise: Option<bool>,
+    mid_side: Option<bool>,
+    description: Option<String>,
+    embed_info: Option<bool>,
+    opus_bitrate: Option<u32>,
+) -> Result<SavedRecording, String> {
+    info!("Stopping recording");
+    if target_sample_rate == Some(0) {
+        return Err("Target sample rate must be greater than zero".to_string());
+    }
+    if let Some(target) = normalize_peak_dbfs {
+        if !target.is_finite() || target > 0.0 {
+            return Err(format!("Invalid normalization target: {} dBFS", target));
+        }
+    }
+    let defaults = saved_stop_options(&app);
+    let options = StopOptions {
+        format: format.unwrap_or(defaults.format),
+        target_sample_rate: target_sample_rate.or(defaults.target_sample_rate),
+        normalize_peak_dbfs,
+        allow_empty: allow_empty.unwrap_or(false),
+        denoise: denoise.unwrap_or(false),
+        mid_side: mid_side.unwrap_or(false),
+        description: description
+            .map(|description| description.trim().to_string())
+            .filter(|description| !description.is_empty()),
+        embed_info: embed_info.unwrap_or(false),
+        opus_bitrate: opus_bitrate.or(defaults.opus_bitrate),
+    };
+    // Fail before the stream is stopped, while the recording can still be
+    // saved in another format.
+    check_opus_options(options.format, options.opus_bitrate)?;
+    let result = finish_recording(&state, &app, options);
+    resume_pre_roll(&state, &app);
+    result
+}
+
+/// Stop options from the saved output format, quality preset and Opus
+/// bitrate. Every way a recording ends starts from these; `stop_recording`
+/// lets its arguments override them. A saved format this build can't write
+/// falls back to WAV rather than losing the recording.
+fn saved_stop_options(app: &AppHandle) -> StopOptions {
+    let options = load_recording_config(app).stop_options();
+    if let Err(e) = check_opus_options(options.format, options.opus_bitrate) {
+        warn!("ignoring saved output options: {}", e);
+        return StopOptions::default();
+    }
+    options
+}
+
+/// Rejects Opus output from a build that can't encode it, and bitrates libopus
+/// won't take.
+fn check_opus_options(format: OutputFormat, bitrate: Option<u32>) -> Result<(), String> {
+    if format == OutputFormat::Opus && !cfg!(feature = "opus") {
+        return Err(audio::OPUS_UNAVAILABLE.to_string());
+    }
+    if let Some(bitrate) = bitrate {
+        if !OPUS_BITRATE_RANGE.contains(&bitrate) {
+            return Err(format!(
+                "Opus bitrate must be between {} and {} bits per second",
+                OPUS_BITRATE_RANGE.start(),
+                OPUS_BITRATE_RANGE.end()
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Returned by `stop_recording` when nothing was captured, so callers can tell
+/// an immediate stop apart from a real failure.
+const EMPTY_RECORDING_ERROR: &str = "Recording is empty; nothing was saved";
+
+/// Stops the stream and writes out the recording. Shared by `stop_recording`
+/// and the max-duration auto-stop, so it only takes the locks it needs.
+fn finish_recording(
+    state: &RecordingState,
+    app: &AppHandle,
+    mut options: StopOptions,
+) -> Result<SavedRecording, String> {
+    // Dropped unused if saving fails, which ends the worker.
+    let live_transcription = transcribe::take_live_transcription(app);
+    let mut stream_guard = state.stream.lock_or_recover();
+    if stream_guard.is_none() {
+        return Err("Recording is not running".to_string());
+    }
+    *stream_guard = None;
+    drop(stream_guard);
+    *state.loopback_stream.lock_or_recover() = None;
+
+    let status = recording_status(state, "stopped");
+    *state.started_at.lock_or_recover() = None;
+
+    let sample_rate = {
+        let guard = state.sample_rate.lock_or_recover();
+        if *guard == 0 {
+            44_100
+        } else {
+            *guard
+        }
+    };
+
+    let output_channels = *state.output_channels.lock_or_recover();
+    let peak = *state.peak.lock_or_recover();
+    let device_name = state.device_name.lock_or_recover().clone();
+
+    // Samples still to be written. A streamed recording is already on disk and
+    // is only read back when the stop options change the audio.
+    let mut pending = None;
+    let mut sample_count = 0;
+
+    let segmented = state.segment_base.lock_or_recover().take().is_some();
+    let mut segments = std::mem::take(&mut *state.segments.lock_or_recover());
+    if segmented && options.rewrites_audio(sample_rate) {
+        // Earlier segments were already handed out as WAV; keep them all alike.
+        warn!("segmented recordings are kept as streamed WAV; ignoring stop options");
+        options = StopOptions {
+            allow_empty: options.allow_empty,
+            description: options.description.take(),
+            embed_info: options.embed_info,
+            ..StopOptions::default()
+        };
+    }
+
+    let streamed = state.writer.lock_or_recover().take();
+    let mut file_path = match streamed {
+        Some(writer) => {
+            sample_count = writer.len() as usize;
+            writer.finalize().map_err(|e| e.to_string())?;
+            let wav_path = state
+                .output_path
+                .lock_or_recover()
+                .take()
+                .ok_or("Recording output path missing")?;
+            if options.rewrites_audio(sample_rate) {
+                pending = Some(read_wav_samples(&wav_path)?);
+                fs::remove_file(&wav_path).map_err(|e| e.to_string())?;
+                wav_path.with_extension(options.format.extension())
+            } else {
+                wav_path
+            }
+        }
+        None => {
+            let mut samples = take_recorded_samples(state);
+            mix_loopback(state, &mut samples, sample_rate, output_channels);
+            pending = Some(samples);
+            let requested = state.requested_path.lock_or_recover().take();
+            match requested {
+                Some(path) => path,
+                None => new_recording_path(app, options.format.extension())?,
+            }
+        }
+    };
+
+    // A rotation right before stopping leaves an empty final segment; drop it
+    // in favour of the last full one.
+    if segmented && sample_count == 0 {
+        if let Some(previous) = segments.pop() {
+            if let Err(e) = fs::remove_file(&file_path) {
+                warn!(
+                    "failed to remove empty segment {}: {}",
+                    file_path.display(),
+                    e
+                );
+            }
+            sample_count = hound::WavReader::open(&previous)
+                .map(|reader| reader.len() as usize)
+                .map_err(|e| format!("Failed to open {}: {}", previous.display(), e))?;
+            file_path = previous;
+        }
+    }
+
+    let captured_nothing = match &pending {
+        Some(samples) => samples.is_empty(),
+        None => sample_count == 0,
+    };
+    if captured_nothing && !options.allow_empty {
+        // Only a streamed file exists at this point; a rewritten or in-memory
+        // recording has not been written yet.
+        if pending.is_none() {
+            if let Err(e) = fs::remove_file(&file_path) {
+                warn!(
+                    "failed to remove empty recording {}: {}",
+                    file_path.display(),
+                    e
+                );
+            }
+        }
+        recovery::discard_recovery_file(state);
+        emit_recording_state(app, status);
+        return Err(EMPTY_RECORDING_ERROR.to_string());
+    }
+
+    let mut output_rate = sample_rate;
+    let mut normalization_gain = None;
+    let mut denoised = false;
+    let mut mid_side = false;
+    let mut bits_per_sample = 16;
+    *state.last_samples.lock_or_recover() = None;
+    let bit_depth = *state.bit_depth.lock_or_recover();
+    let hires = std::mem::take(&mut *state.hires_samples.lock_or_recover());
+    if bit_depth > 16 && pending.is_some() && options.rewrites_audio(sample_rate) {
+        // The stop options work on 16-bit samples.
+        warn!(
+            "stop options rewrite the audio; saving as 16-bit instead of {}-bit",
+            bit_depth
+        );
+    } else if bit_depth > 16 && !hires.is_empty() {
+        write_hires_wav(&file_path, &hires, sample_rate, output_channels, bit_depth)?;
+        bits_per_sample = bit_depth;
+        sample_count = hires.len();
+        if let Some(samples) = pending.take() {
+            *state.last_samples.lock_or_recover() = Some(CachedSamples {
+                samples,
+                channels: output_channels,
+            });
+        }
+    }
+    if let Some(mut samples) = pending {
+        if options.denoise {
+            denoised = audio::spectral_denoise(&mut samples, output_channels, sample_rate);
+            if !denoised {
+                warn!("recording too short for a noise profile; skipping noise reduction");
+            }
+        }
+        if options.mid_side {
+            if output_channels == 2 {
+                audio::stereo_to_mid_side(&mut samples);
+                mid_side = true;
+            } else {
+                warn!("mid/side needs a stereo recording; keeping the mono channel");
+            }
+        }
+        if let Some(target_rate) = options.target_sample_rate {
+            if target_rate != sample_rate {
+                samples = audio::resample_interleaved(
+                    &samples,
+                    output_channels,
+                    sample_rate,
+                    target_rate,
+                );
+                output_rate = target_rate;
+            }
+        }
+        if options.format == OutputFormat::Opus && !audio::OPUS_SAMPLE_RATES.contains(&output_rate)
+        {
+            samples = audio::resample_interleaved(&samples, output_channels, output_rate, 48_000);
+            output_rate = 48_000;
+        }
+        if let Some(target_dbfs) = options.normalize_peak_dbfs {
+            normalization_gain = audio::normalize_peak(&mut samples, target_dbfs);
+        }
+        sample_count = samples.len();
+        match options.format {
+            OutputFormat::Opus => audio::encode_opus(
+                &file_path,
+                &samples,
+                output_rate,
+                output_channels,
+                options.opus_bitrate.unwrap_or(DEFAULT_OPUS_BITRATE),
+            )?,
+            format => write_audio_file(&file_path, &samples, output_rate, output_channels, format)?,
+        }
+        *state.last_samples.lock_or_recover() = Some(CachedSamples {
+            samples,
+            channels: output_channels,
+        });
+    }
+
+    let integrated_lufs = state
+        .last_samples
+        .lock_or_recover()
+        .as_ref()
+        .and_then(|cached| {
+            recording_loudness(&cached.samples, cached.channels, output_rate, mid_side)
+        });
+
+    let saved_location = get_saved_location(app.clone()).ok().flatten();
+    let metadata = RecordingMetadata {
+        sample_rate: output_rate,
+        channels: output_channels,
+        duration_ms: duration_ms(sample_count, output_rate, output_channels),
+        device_name,
+        created_at: unix_millis()?,
+        peak_dbfs: audio::linear_to_dbfs((peak * normalization_gain.unwrap_or(1.0)).min(1.0)),
+        integrated_lufs,
+        format: options.format,
+        normalization_gain,
+        denoised,
+        bits_per_sample,
+        mid_side,
+        tags: Vec::new(),
+        note: options.description.clone(),
+        location: saved_location.as_ref().map(|saved| saved.location.clone()),
+        country: saved_location.and_then(|saved| saved.country),
+        keep: false,
+    };
+    let sidecar_path = write_sidecar(&file_path, &metadata)?;
+    if options.embed_info {
+        if options.format == OutputFormat::Wav {
+            let location = metadata
+                .location
+                .as_ref()
+                .map(|location| match &metadata.country {
+                    Some(country) => format!("{}, {}", location, country),
+                    None => location.clone(),
+                });
+            let info = wav_info::WavInfo {
+                created_at: Some(metadata.created_at),
+                description: options.description.clone(),
+                location,
+            };
+            wav_info::append_info_chunk(&file_path, &info)?;
+        } else {
+            warn!("INFO metadata is only embedded in WAV files; skipping");
+        }
+    }
+
+    if segmented {
+        segments.push(file_path.clone());
+    }
+    if let Some(live) = live_transcription {
+        if let Some(cached) = state.last_samples.lock_or_recover().as_ref() {
+            live.finish(
+                file_path.to_string_lossy().to_string(),
+                &cached.samples,
+                output_rate,
+                cached.channels,
+            );
+        }
+    }
+
+    recovery::discard_recovery_file(state);
+    emit_recording_state(app, status);
+    Ok(SavedRecording {
+        path: file_path.to_string_lossy().to_string(),
+        sidecar_path: sidecar_path.to_string_lossy().to_string(),
+        metadata,
+        segments: segments
+            .iter()
+            .map(|path| path.to_string_lossy().to_string())
+            .collect(),
+    })
+}
+
+/// Integrated loudness of a saved recording's samples. The mid channel is the
+/// mono downmix of a mid/side recording, so only that one is measured.
+fn recording_loudness(
+    samples: &[i16],
+    channels: u16,
+    sample_rate: u32,
+    mid_side: bool,
+) -> Option<f32> {
+    if mid_side {
+        let mid: Vec<i16> = samples.iter().step_by(2).copied().collect();
+        audio::integrated_loudness(&mid, 1, sample_rate)
+    } else {
+        audio::integrated_loudness(samples, channels, sample_rate)
+    }
+}
+
+/// Mixes the system audio of a mixed recording into the microphone samples,
+/// resampled to the microphone's rate. Both streams started together, so they
+/// are aligned from the first frame.
+fn mix_loopback(state: &RecordingState, samples: &mut Vec<i16>, sample_rate: u32, channels: u16) {
+    let loopback_rate = std::mem::take(&mut *state.loopback_rate.lock_or_recover());
+    let loopback = std::mem::take(&mut *state.loopback_samples.lock_or_recover());
+    if loopback_rate == 0 {
+        return;
+    }
+    let loopback = audio::resample_interleaved(&loopback, channels, loopback_rate, sample_rate);
+    audio::mix_into(samples, &loopback);
+}
+
+fn read_wav_samples(path: &Path) -> Result<Vec<i16>, String> {
+    let mut reader = hound::WavReader::open(path)
+        .map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+    reader
+        .samples::<i16>()
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())
+}
+
+fn write_audio_file(
+    path: &Path,
+    samples: &[i16],
+    sample_rate: u32,
+    channels: u16,
+    format: OutputFormat,
+) -> Result<(), String> {
+    match format {
+        OutputFormat::Wav => {
+            let mut writer = create_wav_writer(path, wav_spec(sample_rate, channels))?;
+            for &sample in samples {
+                writer.write_sample(sample).map_err(|e| e.to_string())?;
+            }
+            writer.finalize().map_err(|e| e.to_string())
+        }
+        OutputFormat::Flac => audio::encode_flac(path, samples, sample_rate, channels),
+        OutputFormat::Opus => {
+            audio::encode_opus(path, samples, sample_rate, channels, DEFAULT_OPUS_BITRATE)
+        }
+    }
+}
+
+fn encode_wav_bytes(samples: &[i16], sample_rate: u32, channels: u16) -> Result<Vec<u8>, String> {
+    let mut cursor = Cursor::new(Vec::new());
+    let mut writer = hound::WavWriter::new(&mut cursor, wav_spec(sample_rate, channels))
+        .map_err(|e| e.to_string())?;
+    for &sample in samples {
+        writer.write_sample(sample).map_err(|e| e.to_string())?;
+    }
+    writer.finalize().map_err(|e| e.to_string())?;
+    Ok(cursor.into_inner())
+}
+
+/// Returns the audio captured so far as a base64-encoded WAV without stopping
+/// the recording, e.g. for interim transcription. Only in-memory recordings
+/// can be snapshotted; a streamed one is still being written to disk.
+#[tauri::command]
+fn snapshot_recording(state: State<RecordingState>) -> Result<String, String> {
+    let snapshot = recording_snapshot(&state, None)?;
+    let wav = encode_wav_bytes(&snapshot.samples, snapshot.sample_rate, snapshot.channels)?;
+    Ok(base64::engine::general_purpose::STANDARD.encode(wav))
+}
+
+/// Audio copied out of a running in-memory recording.
+struct RecordingSnapshot {
+    samples: Vec<i16>,
+    sample_rate: u32,
+    channels: u16,
+}
+
+/// Copies the last `max_frames` frames of the recording in progress, or all of
+/// it with `None`. Fails when nothing is recording or the recording is
+/// streamed to disk.
+fn recording_snapshot(
+    state: &RecordingState,
+    max_frames: Option<usize>,
+) -> Result<RecordingSnapshot, String> {
+    if state.stream.lock_or_recover().is_none() {
+        return Err("Recording is not running".to_string());
+    }
+    if state.writer.lock_or_recover().is_some() {
+        return Err("Snapshots are not available while streaming to disk".to_string());
+    }
+    let sample_rate = *state.sample_rate.lock_or_recover();
+    let channels = *state.output_channels.lock_or_recover();
+    // Copy under the lock and encode afterwards so the audio callback is not
+    // held up by the encoding.
+    let start = max_frames.map_or(0, |frames| {
+        recorded_len(state).saturating_sub(frames.saturating_mul(channels as usize))
+    });
+    Ok(RecordingSnapshot {
+        samples: recorded_samples_from(state, start),
+        sample_rate,
+        channels,
+    })
+}
+
+/// Waveform of the last finished recording, one value per frame in -1..=1,
+/// without reading the file back. With `max_points` the frames are grouped
+/// into that many buckets, each reduced to its largest-magnitude sample.
+/// Recordings streamed straight to disk are not cached.
+#[tauri::command]
+fn get_last_samples(
+    state: State<RecordingState>,
+    max_points: Option<usize>,
+) -> Result<Vec<f32>, String> {
+    let cached = state.last_samples.lock_or_recover();
+    let cached = cached
+        .as_ref()
+        .ok_or("No finished recording is cached; streamed recordings are only on disk")?;
+    Ok(audio::peak_buckets(
+        &cached.samples,
+        cached.channels,
+        max_points,
+    ))
+}
+
+fn duration_ms(sample_count: usize, sample_rate: u32, channels: u16) -> u64 {
+    if sample_rate == 0 || channels == 0 {
+        return 0;
+    }
+    let frames = sample_count as u64 / channels as u64;
+    frames * 1000 / sample_rate as u64
+}
+
+fn unix_millis() -> Result<u64, String> {
+    Ok(SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_err(|e| e.to_string())?
+        .as_millis() as u64)
+}
+
+fn sidecar_path(recording_path: &Path) -> PathBuf {
+    recording_path.with_extension("json")
+}
+
+/// Writes `<name>.json` next to the recording so other tools don't need to
+/// decode the audio to learn about it.
+fn write_sidecar(recording_path: &Path, metadata: &RecordingMetadata) -> Result<PathBuf, String> {
+    let path = sidecar_path(recording_path);
+    let data = serde_json::to_string_pretty(metadata).map_err(|e| e.to_string())?;
+    fs::write(&path, data)
+        .map_err(|e| format!("Failed to write metadata to {}: {}", path.display(), e))?;
+    Ok(path)
+}
+
+#[tauri::command]
+fn get_input_gain(state: State<RecordingState>) -> Result<f32, String> {
+    Ok(*state.gain.lock_or_recover())
+}
+
+#[tauri::command]
+fn set_input_gain(state: State<RecordingState>, gain: f32) -> Result<(), String> {
+    if !gain.is_finite() || gain < 0.0 {
+        return Err(format!("Invalid input gain: {}", gain));
+    }
+    *state.gain.lock_or_recover() = gain;
+    Ok(())
+}
+
+#[tauri::command]
+fn get_highpass_cutoff(state: State<RecordingState>) -> Result<f32, String> {
+    Ok(*state.highpass_cutoff.lock_or_recover())
+}
+
+/// Sets the high-pass cutoff in Hz. Zero turns the filter off.
+#[tauri::command]
+fn set_highpass_cutoff(state: State<RecordingState>, cutoff_hz: f32) -> Result<(), String> {
+    if !cutoff_hz.is_finite() || cutoff_hz < 0.0 {
+        return Err(format!("Invalid high-pass cutoff: {}", cutoff_hz));
+    }
+    *state.highpass_cutoff.lock_or_recover() = cutoff_hz;
+    Ok(())
+}
+
+#[tauri::command]
+fn get_level_interval(state: State<RecordingState>) -> Result<u64, String> {
+    Ok(state.level_interval.lock_or_recover().as_millis() as u64)
+}
+
+/// Sets the minimum spacing of `audio-level` events in milliseconds. Zero
+/// emits one per input buffer.
+#[tauri::command]
+fn set_level_interval(state: State<RecordingState>, interval_ms: u64) -> Result<(), String> {
+    *state.level_interval.lock_or_recover() = Duration::from_millis(interval_ms);
+    Ok(())
+}
+
+#[tauri::command]
+fn get_agc(state: State<RecordingState>) -> Result<AgcSettings, String> {
+    Ok(*state.agc.lock_or_recover())
+}
+
+#[tauri::command]
+fn set_agc_enabled(state: State<RecordingState>, enabled: bool) -> Result<(), String> {
+    state.agc.lock_or_recover().enabled = enabled;
+    Ok(())
+}
+
+#[tauri::command]
+fn set_agc_target(state: State<RecordingState>, target_dbfs: f32) -> Result<(), String> {
+    if !target_dbfs.is_finite() || target_dbfs > 0.0 {
+        return Err(format!("Invalid AGC target: {} dBFS", target_dbfs));
+    }
+    state.agc.lock_or_recover().target_dbfs = target_dbfs;
+    Ok(())
+}
+
+#[tauri::command]
+fn get_noise_gate(state: State<RecordingState>) -> Result<NoiseGateSettings, String> {
+    Ok(*state.noise_gate.lock_or_recover())
+}
+
+/// Sets the gate threshold and hold time. A `None` threshold turns the gate
+/// off. Takes effect on the next input buffer.
+#[tauri::command]
+fn set_noise_gate(
+    state: State<RecordingState>,
+    threshold_dbfs: Option<f32>,
+    hold_ms: u64,
+) -> Result<(), String> {
+    if let Some(threshold) = threshold_dbfs {
+        if !threshold.is_finite() || threshold > 0.0 {
+            return Err(format!("Invalid noise gate threshold: {} dBFS", threshold));
+        }
+    }
+    *state.noise_gate.lock_or_recover() = NoiseGateSettings {
+        threshold_dbfs,
+        hold_ms,
+    };
+    Ok(())
+}
+
+/// Applies the settings remembered for the microphone a recording is about to
+/// use, looked up by ID first and then by name.
+fn apply_device_settings(
+    state: &RecordingState,
+    app: &AppHandle,
+    device_id: Option<&str>,
+    device_name: &str,
+) {
+    let config = load_recording_config(app);
+    let settings = device_id
+        .and_then(|id| config.device_settings.get(id))
+        .or_else(|| config.device_settings.get(device_name));
+    if let Some(settings) = settings {
+        info!(device = %device_name, "Applying remembered device settings");
+        settings.apply(state);
+    }
+}
+
+#[tauri::command]
+fn get_device_settings(app: AppHandle, device: String) -> Result<Option<DeviceSettings>, String> {
+    Ok(load_recording_config(&app)
+        .device_settings
+        .get(&device)
+        .copied())
+}
+
+/// Remembers gain, noise gate and high-pass cutoff for `device`, a
+/// `DeviceInfo::id` or device name, to be applied whenever a recording starts
+/// on it. Leaving all three out forgets the device.
+#[tauri::command]
+fn set_device_settings(
+    app: AppHandle,
+    device: String,
+    gain: Option<f32>,
+    noise_gate: Option<NoiseGateSettings>,
+    highpass_cutoff_hz: Option<f32>,
+) -> Result<(), String> {
+    let settings = DeviceSettings {
+        gain,
+        noise_gate,
+        highpass_cutoff_hz,
+    };
+    settings.validate()?;
+    let mut config = load_recording_config(&app);
+    if gain.is_none() && noise_gate.is_none() && highpass_cutoff_hz.is_none() {
+        config.device_settings.remove(&device);
+    } else {
+        config.device_settings.insert(device, settings);
+    }
+    save_recording_config(&app, &config)
+}
+
+#[tauri::command]
+fn get_limiter(state: State<RecordingState>) -> Result<LimiterSettings, String> {
+    Ok(*state.limiter.lock_or_recover())
+}
+
+#[tauri::command]
+fn set_limiter_enabled(state: State<RecordingState>, enabled: bool) -> Result<(), String> {
+    state.limiter.lock_or_recover().enabled = enabled;
+    Ok(())
+}
+
+#[tauri::command]
+fn set_limiter_params(
+    state: State<RecordingState>,
+    threshold_dbfs: f32,
+    release_ms: f32,
+) -> Result<(), String> {
+    if !threshold_dbfs.is_finite() || threshold_dbfs > 0.0 {
+        return Err(format!(
+            "Invalid limiter threshold: {} dBFS",
+            threshold_dbfs
+        ));
+    }
+    if !release_ms.is_finite() || release_ms < 0.0 {
+        return Err(format!("Invalid limiter release: {} ms", release_ms));
+    }
+    let mut limiter = state.limiter.lock_or_recover();
+    limiter.threshold_dbfs = threshold_dbfs;
+    limiter.release_ms = release_ms;
+    Ok(())
+}
+
+#[tauri::command]
+fn get_meter_ballistics(state: State<RecordingState>) -> Result<audio::MeterBallistics, String> {
+    Ok(*state.meter_ballistics.lock_or_recover())
+}
+
+/// Sets the time constants behind the smoothed `audio-level` values, in
+/// milliseconds.
+#[tauri::command]
+fn set_meter_ballistics(
+    state: State<RecordingState>,
+    rms_attack_ms: f32,
+    rms_release_ms: f32,
+    peak_attack_ms: f32,
+    peak_release_ms: f32,
+    peak_hold_ms: f32,
+) -> Result<(), String> {
+    for (name, value) in [
+        ("RMS attack", rms_attack_ms),
+        ("RMS release", rms_release_ms),
+        ("peak attack", peak_attack_ms),
+        ("peak release", peak_release_ms),
+        ("peak hold", peak_hold_ms),
+    ] {
+        if !value.is_finite() || value < 0.0 {
+            return Err(format!("Invalid meter {}: {} ms", name, value));
+        }
+    }
+    *state.meter_ballistics.lock_or_recover() = audio::MeterBallistics {
+        rms_attack_ms,
+        rms_release_ms,
+        peak_attack_ms,
+        peak_release_ms,
+        peak_hold_ms,
+    };
+    Ok(())
+}
+
+#[tauri::command]
+fn get_level_thresholds(state: State<RecordingState>) -> Result<audio::LevelThresholds, String> {
+    Ok(*state.level_thresholds.lock_or_recover())
+}
+
+/// Sets the peak levels, in dBFS, at which `audio-level`'s `level_status`
+/// becomes "low", "good", "hot" and "clipping".
+#[tauri::command]
+fn set_level_thresholds(
+    state: State<RecordingState>,
+    low_dbfs: f32,
+    good_dbfs: f32,
+    hot_dbfs: f32,
+    clipping_dbfs: f32,
+) -> Result<(), String> {
+    let thresholds = audio::LevelThresholds {
+        low_dbfs,
+        good_dbfs,
+        hot_dbfs,
+        clipping_dbfs,
+    };
+    thresholds.validate()?;
+    *state.level_thresholds.lock_or_recover() = thresholds;
+    Ok(())
+}
+
+#[tauri::command]
+fn get_silence_detection(state: State<RecordingState>) -> Result<SilenceDetection, String> {
+    Ok(*state.silence.lock_or_recover())
+}
+
+#[tauri::command]
+fn set_silence_detection(
+    state: State<RecordingState>,
+    threshold_dbfs: f32,
+    timeout_ms: u64,
+) -> Result<(), String> {
+    if !threshold_dbfs.is_finite() {
+        return Err(format!("Invalid silence threshold: {}", threshold_dbfs));
+    }
+    *state.silence.lock_or_recover() = SilenceDetection {
+        threshold_dbfs,
+        timeout_ms,
+    };
+    Ok(())
+}
+
+#[tauri::command]
+fn pause_recording(state: State<RecordingState>, app: AppHandle) -> Result<(), String> {
+    info!("Pausing recording");
+    let stream_guard = state.stream.lock_or_recover();
+    if stream_guard.is_none() {
+        return Err("Recording is not running".to_string());
+    }
+    state.paused.store(true, Ordering::Relaxed);
+    emit_recording_state(&app, recording_status(&state, "paused"));
+    Ok(())
+}
+
+#[tauri::command]
+fn resume_recording(state: State<RecordingState>, app: AppHandle) -> Result<(), String> {
+    info!("Resuming recording");
+    let stream_guard = state.stream.lock_or_recover();
+    if stream_guard.is_none() {
+        return Err("Recording is not running".to_string());
+    }
+    state.paused.store(false, Ordering::Relaxed);
+    emit_recording_state(&app, recording_status(&state, "recording"));
+    Ok(())
+}
+
+/// Replaces the input with silence without pausing, so the recording keeps
+/// running and its timeline stays aligned with wall-clock time. Useful to
+/// redact a private stretch of a meeting.
+#[tauri::command]
+fn set_mute(state: State<RecordingState>, app: AppHandle, muted: bool) -> Result<(), String> {
+    info!(muted, "Setting recording mute");
+    let stream_guard = state.stream.lock_or_recover();
+    if stream_guard.is_none() {
+        return Err("Recording is not running".to_string());
+    }
+    state.muted.store(muted, Ordering::Relaxed);
+    if let Err(e) = app.emit("mute-changed", MuteEvent { muted }) {
+        error!("failed to emit mute-changed: {:?}", e);
+    }
+    Ok(())
+}
+
+#[tauri::command]
+fn cancel_recording(state: State<RecordingState>, app: AppHandle) -> Result<(), String> {
+    cancel_capture(&state, &app)
+}
+
+/// Stops the stream and throws the recording away. Shared by
+/// `cancel_recording` and the cancel shortcut.
+fn cancel_capture(state: &RecordingState, app: &AppHandle) -> Result<(), String> {
+    info!("Canceling recording");
+    let mut stream_guard = state.stream.lock_or_recover();
+    *stream_guard = None;
+    drop(stream_guard);
+    *state.loopback_stream.lock_or_recover() = None;
+
+    let sample_rate = *state.sample_rate.lock_or_recover();
+    let channels = *state.output_channels.lock_or_recover();
+    let mut samples = take_recorded_samples(state);
+    state.hires_samples.lock_or_recover().clear();
+    // A mixed recording is kept with the system audio already mixed in.
+    mix_loopback(state, &mut samples, sample_rate, channels);
+
+    // A streamed recording already has a partial file on disk. The writer
+    // finalizes it when dropped, so it is kept as is until the undo window
+    // closes.
+    let streamed = state.writer.lock_or_recover().take();
+    let output_path = state.output_path.lock_or_recover().take();
+    let mut audio = CancelledAudio::Samples(samples);
+    if let (Some(writer), Some(path)) = (streamed, output_path) {
+        drop(writer);
+        audio = CancelledAudio::File(path);
+    }
+    // Segments were already handed out as separate files; those are not kept.
+    let segmented = state.segment_base.lock_or_recover().take().is_some();
+    for segment in state.segments.lock_or_recover().drain(..) {
+        let _ = fs::remove_file(segment);
+    }
+    if segmented {
+        if let CancelledAudio::File(path) = &audio {
+            let _ = fs::remove_file(path);
+        }
+        audio = CancelledAudio::Samples(Vec::new());
+    }
+
+    let recording = CancelledRecording {
+        audio,
+        sample_rate,
+        channels,
+        device_name: state.device_name.lock_or_recover().clone(),
+        peak: *state.peak.lock_or_recover(),
+        cancelled_at: Instant::now(),
+    };
+    let has_audio = match &recording.audio {
+        CancelledAudio::Samples(samples) => !samples.is_empty(),
+        CancelledAudio::File(_) => true,
+    };
+    // A stray second cancel must not wipe out the recording set aside by
+    // the first.
+    if has_audio {
+        discard_cancelled_recording(&state.cancelled);
+        *state.cancelled.lock_or_recover() = Some(recording);
+        expire_cancelled_recording(state.cancelled.clone());
+    }
+
+    // Stops live transcription without a final pass.
+    drop(transcribe::take_live_transcription(app));
+    recovery::discard_recovery_file(state);
+    emit_recording_state(app, recording_status(state, "cancelled"));
+    *state.started_at.lock_or_recover() = None;
+    resume_pre_roll(state, app);
+    Ok(())
+}
+
+/// Peak a microphone test has to reach to count as hearing something. Well
+/// above the self-noise of a working input, well below quiet speech.
+const MIC_TEST_FLOOR_DBFS: f32 = -50.0;
+/// Longest allowed `mic_test`.
+const MAX_MIC_TEST_MS: u32 = 10_000;
+/// Points in `MicTestResult::waveform`.
+const MIC_TEST_WAVEFORM_POINTS: usize = 64;
+
+/// Input stream for `mic_test`: the same `process_input` as a recording, fed
+/// into `scratch`. Unlike `build_input_stream`, a lost device only ends the
+/// test instead of saving a recording.
+fn build_mic_test_stream(
+    input: &OpenedInput,
+    capture: CaptureSettings,
+    scratch: &RecordingState,
+    app: &AppHandle,
+) -> Result<cpal::Stream, String> {
+    let recording = scratch.clone();
+    let app = app.clone();
+    let on_error = |err| warn!("mic test stream error: {}", err);
+    match input.sample_format {
+        SampleFormat::F32 => input.device.build_input_stream(
+            &input.config,
+            move |data: &[f32], _: &_| process_input_f32(data, &capture, &recording, &app),
+            on_error,
+            None,
+        ),
+        SampleFormat::I16 => input.device.build_input_stream(
+            &input.config,
+            move |data: &[i16], _: &_| process_input_i16(data, &capture, &recording, &app),
+            on_error,
+            None,
+        ),
+        SampleFormat::U16 => input.device.build_input_stream(
+            &input.config,
+            move |data: &[u16], _: &_| process_input_u16(data, &capture, &recording, &app),
+            on_error,
+            None,
+        ),
+        _ => return Err("Unsupported sample format".to_string()),
+    }
+    .map_err(|e| e.to_string())
+}
+
+/// A blank recorder state carrying the live one's processing settings, so
+/// `mic_test` hears what a recording would without disturbing the recorder.
+fn mic_test_state(state: &RecordingState) -> RecordingState {
+    let scratch = RecordingState::new();
+    *scratch.gain.lock_or_recover() = *state.gain.lock_or_recover();
+    *scratch.highpass_cutoff.lock_or_recover() = *state.highpass_cutoff.lock_or_recover();
+    *scratch.agc.lock_or_recover() = *state.agc.lock_or_recover();
+    *scratch.noise_gate.lock_or_recover() = *state.noise_gate.lock_or_recover();
+    *scratch.limiter.lock_or_recover() = *state.limiter.lock_or_recover();
+    *scratch.level_interval.lock_or_recover() = *state.level_interval.lock_or_recover();
+    *scratch.meter_ballistics.lock_or_recover() = *state.meter_ballistics.lock_or_recover();
+    *scratch.level_thresholds.lock_or_recover() = *state.level_thresholds.lock_or_recover();
+    // A silent microphone is what the result itself reports.
+    scratch.silence.lock_or_recover().timeout_ms = u64::MAX;
+    scratch
+}
+
+#[derive(serde::Serialize, Clone)]
+struct MicTestResult {
+    /// The peak reached `MIC_TEST_FLOOR_DBFS`.
+    detected: bool,
+    peak_dbfs: f32,
+    rms_dbfs: f32,
+    /// Largest-magnitude sample per bucket, in -1..=1; see `get_last_samples`.
+    waveform: Vec<f32>,
+}
+
+/// Records from the configured microphone for `duration_ms` and reports
+/// whether it picked anything up, without saving a file. Fails if a recording
+/// or another test is running, and recordings can't start until it ends.
+///
+/// The audio goes through the recording pipeline (gain, high-pass, AGC, noise
+/// gate, limiter), but into a scratch state, so the recorder's state, the
+/// saved device and an undoable cancel are left untouched. A monitor or
+/// pre-roll stream is closed for the test, and pre-roll resumes afterwards.
+#[tauri::command]
+async fn mic_test(app: AppHandle, duration_ms: u32) -> Result<MicTestResult, String> {
+    if duration_ms == 0 || duration_ms > MAX_MIC_TEST_MS {
+        return Err(format!(
+            "Mic test length must be between 1 and {} ms",
+            MAX_MIC_TEST_MS
+        ));
+    }
+    tauri::async_runtime::spawn_blocking(move || {
+        let state = app.state::<RecordingState>();
+        {
+            // Checked under the stream lock, like `begin_capture` does.
+            let stream_guard = state.stream.lock_or_recover();
+            if stream_guard.is_some() {
+                return Err("A recording is already running".to_string());
+            }
+            if state.mic_testing.swap(true, Ordering::SeqCst) {
+                return Err("A mic test is already running".to_string());
+            }
+        }
+        // The monitor would hold the same device open.
+        close_monitor(&state, &app);
+        let result = run_mic_test(&state, &app, duration_ms);
+        state.mic_testing.store(false, Ordering::SeqCst);
+        resume_pre_roll(&state, &app);
+        result
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+fn run_mic_test(
+    state: &RecordingState,
+    app: &AppHandle,
+    duration_ms: u32,
+) -> Result<MicTestResult, String> {
+    let options = load_recording_config(app).start_options();
+    let host = cpal::default_host();
+    let input = open_input_device(&host, app, &options)?;
+    let capture = CaptureSettings {
+        channel_select: options.selected_channel(input.config.channels),
+        ..CaptureSettings::for_stream(&input.config, ChannelMode::Mono)
+    };
+    let scratch = mic_test_state(state);
+    apply_device_settings(&scratch, app, options.device_id.as_deref(), &input.name);
+    reset_capture_state(&scratch, &capture, input.name.clone(), None);
+
+    let stream = build_mic_test_stream(&input, capture, &scratch, app)?;
+    stream.play().map_err(|e| e.to_string())?;
+    std::thread::sleep(Duration::from_millis(duration_ms as u64));
+    drop(stream);
+
+    let samples = std::mem::take(&mut *scratch.samples.lock_or_recover());
+    let normalized: Vec<f32> = samples
+        .iter()
+        .map(|&sample| sample as f32 / i16::MAX as f32)
+        .collect();
+    let stats = audio::analyze(&normalized, 1, input.config.sample_rate.0);
+    info!(peak_dbfs = stats.peak_dbfs, "Mic test finished");
+    Ok(MicTestResult {
+        detected: stats.peak_dbfs >= MIC_TEST_FLOOR_DBFS,
+        peak_dbfs: stats.peak_dbfs,
+        rms_dbfs: stats.rms_dbfs,
+        waveform: audio::peak_buckets(&samples, 1, Some(MIC_TEST_WAVEFORM_POINTS)),
+    })
+}
+
+/// How long `undo_cancel` can bring back a cancelled recording.
+const UNDO_CANCEL_WINDOW: Duration = Duration::from_secs(30);
+
+/// Audio set aside by `cancel_capture` until `UNDO_CANCEL_WINDOW` passes or
+/// the next recording starts.
+struct CancelledRecording {
+    audio: CancelledAudio,
+    sample_rate: u32,
+    channels: u16,
+    device_name: Option<String>,
+    peak: f32,
+    cancelled_at: Instant,
 }
 
-fn log_stream_error(err: cpal::StreamError) {
-    eprintln!("an error occurred on stream: {}", err);
+enum CancelledAudio {
+    Samples(Vec<i16>),
+    /// A recording streamed to disk, left in place until restored or freed.
+    File(PathBuf),
 }
 
-fn process_input_f32(
-    data: &[f32],
-    channels: usize,
-    samples: &Arc<Mutex<Vec<i16>>>,
-    window: &Window,
-) {
-    if data.is_empty() || channels == 0 {
+/// Frees the cancelled recording, deleting its file if it was streamed.
+fn discard_cancelled_recording(cancelled: &Mutex<Option<CancelledRecording>>) {
+    let Some(recording) = cancelled.lock_or_recover().take() else {
         return;
+    };
+    if let CancelledAudio::File(path) = recording.audio {
+        if let Err(e) = fs::remove_file(&path) {
+            warn!(
+                "failed to remove cancelled recording {}: {}",
+                path.display(),
+                e
+            );
+        }
     }
+}
 
-    let mut sum_squares = 0.0f32;
-    let mut peak = 0.0f32;
-    let mut mono_samples = Vec::with_capacity(data.len() / channels);
+/// Frees the cancelled recording once the undo window has passed, unless it
+/// was restored or replaced by a newer one in the meantime.
+fn expire_cancelled_recording(cancelled: Arc<Mutex<Option<CancelledRecording>>>) {
+    std::thread::spawn(move || {
+        std::thread::sleep(UNDO_CANCEL_WINDOW);
+        let expired = cancelled
+            .lock_or_recover()
+            .as_ref()
+            .is_some_and(|recording| recording.cancelled_at.elapsed() >= UNDO_CANCEL_WINDOW);
+        if expired {
+            discard_cancelled_recording(&cancelled);
+        }
+    });
+}
 
-    for frame in data.chunks(channels) {
-        if frame.len() < channels {
-            break;
+/// Saves the recording thrown away by the last cancel, if that was less than
+/// `UNDO_CANCEL_WINDOW` ago and no recording has started since. It is saved
+/// as a plain WAV, like a `stop_recording` without options.
+#[tauri::command]
+fn undo_cancel(state: State<RecordingState>, app: AppHandle) -> Result<SavedRecording, String> {
+    let recording = state.cancelled.lock_or_recover().take();
+    let Some(recording) = recording else {
+        return Err("There is no cancelled recording to restore".to_string());
+    };
+    if recording.cancelled_at.elapsed() >= UNDO_CANCEL_WINDOW {
+        *state.cancelled.lock_or_recover() = Some(recording);
+        discard_cancelled_recording(&state.cancelled);
+        return Err("The cancelled recording has expired".to_string());
+    }
+
+    let (file_path, sample_count, integrated_lufs) = match recording.audio {
+        CancelledAudio::Samples(samples) => {
+            let path = new_recording_path(&app, OutputFormat::Wav.extension())?;
+            write_audio_file(
+                &path,
+                &samples,
+                recording.sample_rate,
+                recording.channels,
+                OutputFormat::Wav,
+            )?;
+            let integrated_lufs =
+                recording_loudness(&samples, recording.channels, recording.sample_rate, false);
+            (path, samples.len(), integrated_lufs)
         }
-        let mut acc = 0.0f32;
-        for &sample in frame {
-            let clamped = sample.clamp(-1.0, 1.0);
-            let abs_sample = clamped.abs();
-            if abs_sample > peak {
-                peak = abs_sample;
-            }
-            sum_squares += clamped * clamped;
-            acc += clamped;
+        CancelledAudio::File(path) => {
+            let sample_count = hound::WavReader::open(&path)
+                .map(|reader| reader.len() as usize)
+                .map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+            (path, sample_count, None)
         }
-        let mono = acc / channels as f32;
-        let mono_i16 = (mono.clamp(-1.0, 1.0) * i16::MAX as f32) as i16;
-        mono_samples.push(mono_i16);
-    }
+    };
 
-    let rms = (sum_squares / data.len() as f32).sqrt();
+    let saved_location = get_saved_location(app.clone()).ok().flatten();
+    let metadata = RecordingMetadata {
+        sample_rate: recording.sample_rate,
+        channels: recording.channels,
+        duration_ms: duration_ms(sample_count, recording.sample_rate, recording.channels),
+        device_name: recording.device_name,
+        created_at: unix_millis()?,
+        peak_dbfs: audio::linear_to_dbfs(recording.peak.min(1.0)),
+        integrated_lufs,
+        format: OutputFormat::Wav,
+        normalization_gain: None,
+        denoised: false,
+        bits_per_sample: 16,
+        mid_side: false,
+        tags: Vec::new(),
+        note: None,
+        location: saved_location.as_ref().map(|saved| saved.location.clone()),
+        country: saved_location.and_then(|saved| saved.country),
+        keep: false,
+    };
+    let sidecar_path = write_sidecar(&file_path, &metadata)?;
+    info!("Restored cancelled recording to {}", file_path.display());
+    Ok(SavedRecording {
+        path: file_path.to_string_lossy().to_string(),
+        sidecar_path: sidecar_path.to_string_lossy().to_string(),
+        metadata,
+        segments: Vec::new(),
+    })
+}
 
-    if let Ok(mut guard) = samples.lock() {
-        guard.extend(mono_samples);
-    }
+fn config_file_path(app: &AppHandle, file_name: &str) -> Result<PathBuf, String> {
+    let dir = app.path().app_config_dir().map_err(|e| e.to_string())?;
+    fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+    Ok(dir.join(file_name))
+}
 
-    let _ = window.emit("audio-level", AudioLevel { rms, peak });
+fn location_file_path(app: &AppHandle) -> Result<PathBuf, String> {
+    config_file_path(app, "location.json")
 }
 
-fn process_input_i16(
-    data: &[i16],
-    channels: usize,
-    samples: &Arc<Mutex<Vec<i16>>>,
-    window: &Window,
-) {
-    if data.is_empty() || channels == 0 {
-        return;
+fn settings_file_path(app: &AppHandle) -> Result<PathBuf, String> {
+    config_file_path(app, "settings.json")
+}
+
+fn load_settings(app: &AppHandle) -> Result<AppSettings, String> {
+    let path = settings_file_path(app)?;
+    if !path.exists() {
+        return Ok(AppSettings::default());
     }
+    let contents = fs::read_to_string(path).map_err(|e| e.to_string())?;
+    serde_json::from_str(&contents).map_err(|e| e.to_string())
+}
 
-    let mut sum_squares = 0.0f32;
-    let mut peak = 0.0f32;
-    let mut mono_samples = Vec::with_capacity(data.len() / channels);
+fn save_settings(app: &AppHandle, settings: &AppSettings) -> Result<(), String> {
+    let path = settings_file_path(app)?;
+    let data = serde_json::to_string_pretty(settings).map_err(|e| e.to_string())?;
+    fs::write(path, data).map_err(|e| e.to_string())?;
+    Ok(())
+}
 
-    for frame in data.chunks(channels) {
-        if frame.len() < channels {
-            break;
-        }
-        let mut acc = 0.0f32;
-        for &sample in frame {
-            let normalized = sample as f32 / i16::MAX as f32;
-            let abs_sample = normalized.abs();
-            if abs_sample > peak {
-                peak = abs_sample;
-            }
-            sum_squares += normalized * normalized;
-            acc += normalized;
+fn recording_config_file_path(app: &AppHandle) -> Result<PathBuf, String> {
+    config_file_path(app, "recording_config.json")
+}
+
+/// Saved recording options, or the defaults when there are none or they
+/// cannot be read; recording should never fail over a stale config file.
+fn load_recording_config(app: &AppHandle) -> RecordingConfig {
+    let path = match recording_config_file_path(app) {
+        Ok(path) => path,
+        Err(e) => {
+            error!("failed to resolve recording config path: {}", e);
+            return RecordingConfig::default();
         }
-        let mono = acc / channels as f32;
-        let mono_i16 = (mono.clamp(-1.0, 1.0) * i16::MAX as f32) as i16;
-        mono_samples.push(mono_i16);
+    };
+    if !path.exists() {
+        return RecordingConfig::default();
     }
+    fs::read_to_string(&path)
+        .map_err(|e| e.to_string())
+        .and_then(|contents| serde_json::from_str(&contents).map_err(|e| e.to_string()))
+        .unwrap_or_else(|e| {
+            warn!("could not read {}: {}", path.display(), e);
+            RecordingConfig::default()
+        })
+}
 
-    let rms = (sum_squares / data.len() as f32).sqrt();
+#[tauri::command]
+fn get_recording_config(app: AppHandle) -> RecordingConfig {
+    load_recording_config(&app)
+}
 
-    if let Ok(mut guard) = samples.lock() {
-        guard.extend(mono_samples);
+#[tauri::command]
+fn set_recording_config(
+    app: AppHandle,
+    state: State<RecordingState>,
+    mut config: RecordingConfig,
+) -> Result<(), String> {
+    if let Some(template) = &config.filename_template {
+        let sample = filename::TemplateValues {
+            timestamp: 0,
+            location: None,
+            country: None,
+        };
+        filename::expand_template(template, &sample)?;
+    }
+    check_opus_options(
+        config.output_format.unwrap_or_default(),
+        config.opus_bitrate,
+    )?;
+    if config.retention_days == Some(0) {
+        return Err("Retention must be at least one day".to_string());
+    }
+    if let Some(delay_ms) = config.start_delay_ms {
+        check_start_delay(delay_ms)?;
+    }
+    if let Some(gain) = config.gain {
+        if !gain.is_finite() || gain < 0.0 {
+            return Err(format!("Invalid input gain: {}", gain));
+        }
+        *state.gain.lock_or_recover() = gain;
     }
+    let saved = load_recording_config(&app);
+    config.last_device_name = saved.last_device_name;
+    config.device_settings = saved.device_settings;
+    save_recording_config(&app, &config)
+}
 
-    let _ = window.emit("audio-level", AudioLevel { rms, peak });
+fn save_recording_config(app: &AppHandle, config: &RecordingConfig) -> Result<(), String> {
+    let path = recording_config_file_path(app)?;
+    let data = serde_json::to_string_pretty(config).map_err(|e| e.to_string())?;
+    fs::write(path, data).map_err(|e| e.to_string())
 }
 
-fn process_input_u16(
-    data: &[u16],
-    channels: usize,
-    samples: &Arc<Mutex<Vec<i16>>>,
-    window: &Window,
-) {
-    if data.is_empty() || channels == 0 {
-        return;
-    }
+/// Configured recordings directory, falling back to the OS temp dir.
+fn recordings_dir(app: &AppHandle) -> Result<PathBuf, String> {
+    Ok(load_settings(app)?
+        .recordings_dir
+        .unwrap_or_else(std::env::temp_dir))
+}
 
-    let mut sum_squares = 0.0f32;
-    let mut peak = 0.0f32;
-    let mut mono_samples = Vec::with_capacity(data.len() / channels);
+#[tauri::command]
+fn get_recordings_dir(app: AppHandle) -> Result<String, String> {
+    Ok(recordings_dir(&app)?.to_string_lossy().to_string())
+}
 
-    for frame in data.chunks(channels) {
-        if frame.len() < channels {
-            break;
-        }
-        let mut acc = 0.0f32;
-        for &sample in frame {
-            let normalized = (sample as f32 / u16::MAX as f32) * 2.0 - 1.0;
-            let abs_sample = normalized.abs();
-            if abs_sample > peak {
-                peak = abs_sample;
+/// Absolute path of the directory new recordings are saved to, created if
+/// needed, so the UI can tell users where to find their files.
+#[tauri::command]
+fn get_default_recordings_path(app: AppHandle) -> Result<String, String> {
+    let dir = create_recordings_dir(&app)?;
+    let dir = dir
+        .canonicalize()
+        .map_err(|e| format!("Failed to resolve {}: {}", dir.display(), e))?;
+    Ok(dir.to_string_lossy().to_string())
+}
+
+/// Passing `None` resets the recordings directory to the temp dir.
+#[tauri::command]
+fn set_recordings_dir(app: AppHandle, dir: Option<String>) -> Result<(), String> {
+    let mut settings = load_settings(&app)?;
+    settings.recordings_dir = dir.filter(|dir| !dir.trim().is_empty()).map(PathBuf::from);
+    save_settings(&app, &settings)
+}
+
+#[tauri::command]
+fn get_saved_location(app: AppHandle) -> Result<Option<LocationSettings>, String> {
+    let path = location_file_path(&app)?;
+    if !path.exists() {
+        return Ok(None);
+    }
+    let contents = fs::read_to_string(&path).map_err(|e| e.to_string())?;
+    match serde_json::from_str::<LocationSettings>(&contents) {
+        Ok(settings) => Ok(Some(settings)),
+        Err(e) => {
+            // Keep the unreadable file around for inspection instead of failing
+            // every load until the user re-enters their location.
+            let backup = path.with_extension("json.bak");
+            warn!(
+                "could not parse {} ({}); moving it to {}",
+                path.display(),
+                e,
+                backup.display()
+            );
+            if let Err(e) = fs::rename(&path, &backup) {
+                warn!("failed to back up {}: {}", path.display(), e);
             }
-            sum_squares += normalized * normalized;
-            acc += normalized;
+            Ok(None)
         }
-        let mono = acc / channels as f32;
-        let mono_i16 = (mono.clamp(-1.0, 1.0) * i16::MAX as f32) as i16;
-        mono_samples.push(mono_i16);
     }
+}
 
-    let rms = (sum_squares / data.len() as f32).sqrt();
+/// Guesses the user's location from the OS locale and timezone so the
+/// frontend can offer it for confirmation. Nothing is persisted.
+#[tauri::command]
+fn detect_location() -> LocationSettings {
+    let timezone = iana_time_zone::get_timezone().ok();
+    let country = sys_locale::get_locale()
+        .or_else(|| std::env::var("LANG").ok())
+        .and_then(|locale| country_from_locale(&locale));
+    let location = timezone
+        .as_deref()
+        .and_then(city_from_timezone)
+        .unwrap_or_default();
 
-    if let Ok(mut guard) = samples.lock() {
-        guard.extend(mono_samples);
+    LocationSettings {
+        location,
+        country,
+        timezone,
     }
+}
+
+/// Extracts the region from locales like "en-US" or "en_GB.UTF-8".
+fn country_from_locale(locale: &str) -> Option<String> {
+    let locale = locale.split(['.', '@']).next()?;
+    locale
+        .split(['-', '_'])
+        .skip(1)
+        .find(|part| part.len() == 2 && part.chars().all(|c| c.is_ascii_alphabetic()))
+        .map(|part| part.to_ascii_uppercase())
+}
 
-    let _ = window.emit("audio-level", AudioLevel { rms, peak });
+/// "America/New_York" -> "New York". Zones without a city (e.g. "UTC") yield
+/// nothing.
+fn city_from_timezone(timezone: &str) -> Option<String> {
+    let (_, city) = timezone.rsplit_once('/')?;
+    Some(city.replace('_', " "))
 }
 
 #[tauri::command]
-fn start_recording(state: State<RecordingState>, window: Window) -> Result<(), String> {
-    println!("Starting recording");
-    let mut stream_guard = state.stream.lock().map_err(|e| e.to_string())?;
+fn set_saved_location(app: AppHandle, location: LocationSettings) -> Result<(), String> {
+    let path = location_file_path(&app)?;
+    let data = serde_json::to_string_pretty(&location).map_err(|e| e.to_string())?;
+    fs::write(path, data).map_err(|e| e.to_string())?;
+    Ok(())
+}
 
-    if stream_guard.is_some() {
-        println!("Recording already running");
-        return Ok(());
-    }
+fn parse_shortcut(spec: &str) -> Result<Shortcut, String> {
+    spec.parse::<Shortcut>()
+        .map_err(|e| format!("Invalid shortcut \"{}\": {}", spec, e))
+}
 
-    let host = cpal::default_host();
-    let device = host
-        .default_input_device()
-        .ok_or("No input device available")?;
+fn configured_shortcut(settings: &AppSettings) -> &str {
+    settings.shortcut.as_deref().unwrap_or(DEFAULT_SHORTCUT)
+}
 
-    println!("Using input device: {}", device.name().unwrap_or_default());
+#[tauri::command]
+fn get_shortcut(app: AppHandle) -> Result<String, String> {
+    Ok(configured_shortcut(&load_settings(&app)?).to_string())
+}
 
-    let supported_config = device.default_input_config().map_err(|e| e.to_string())?;
-    let sample_format = supported_config.sample_format();
-    let config: cpal::StreamConfig = supported_config.into();
-    let channels = config.channels as usize;
+#[tauri::command]
+fn set_shortcut(app: AppHandle, shortcut: String) -> Result<(), String> {
+    let shortcut = shortcut.trim().to_string();
+    let new_shortcut = parse_shortcut(&shortcut)?;
+    let mut settings = load_settings(&app)?;
+    let old_shortcut = parse_shortcut(configured_shortcut(&settings)).ok();
+    let bound_to_action = parse_action_shortcuts(&settings)
+        .unwrap_or_default()
+        .iter()
+        .any(|(bound, _)| *bound == new_shortcut);
+    if bound_to_action {
+        return Err(format!(
+            "Shortcut \"{}\" is already bound to an action",
+            shortcut
+        ));
+    }
 
-    {
-        let mut rate_guard = state.sample_rate.lock().map_err(|e| e.to_string())?;
-        *rate_guard = config.sample_rate.0;
-        let mut samples_guard = state.samples.lock().map_err(|e| e.to_string())?;
-        samples_guard.clear();
+    let global_shortcut = app.global_shortcut();
+    if let Some(old) = old_shortcut {
+        if global_shortcut.is_registered(old) {
+            global_shortcut.unregister(old).map_err(|e| e.to_string())?;
+        }
+    }
+    if let Err(e) = global_shortcut.register(new_shortcut) {
+        // Keep the previous shortcut working if the new one is taken.
+        if let Some(old) = old_shortcut {
+            let _ = global_shortcut.register(old);
+        }
+        return Err(format!(
+            "Failed to register shortcut \"{}\": {}",
+            shortcut, e
+        ));
     }
 
-    let samples = state.samples.clone();
-    let window = Arc::new(window);
+    settings.shortcut = Some(shortcut);
+    save_settings(&app, &settings)
+}
 
-    let stream = match sample_format {
-        SampleFormat::F32 => {
-            let samples = samples.clone();
-            let window = window.clone();
-            device.build_input_stream(
-                &config,
-                move |data: &[f32], _: &_| {
-                    process_input_f32(data, channels, &samples, &window);
-                },
-                log_stream_error,
-                None,
-            )
-        }
-        SampleFormat::I16 => {
-            let samples = samples.clone();
-            let window = window.clone();
-            device.build_input_stream(
-                &config,
-                move |data: &[i16], _: &_| {
-                    process_input_i16(data, channels, &samples, &window);
-                },
-                log_stream_error,
-                None,
-            )
+/// Parses the action shortcuts, rejecting accelerators that are bound twice,
+/// either to two actions or to an action and the main shortcut. Keys are
+/// compared after parsing, so "alt+e" and "Alt+E" count as the same.
+fn parse_action_shortcuts(
+    settings: &AppSettings,
+) -> Result<Vec<(Shortcut, ShortcutAction)>, String> {
+    let main = parse_shortcut(configured_shortcut(settings)).ok();
+    let mut parsed: Vec<(Shortcut, ShortcutAction)> = Vec::new();
+    for (spec, action) in &settings.action_shortcuts {
+        let shortcut = parse_shortcut(spec.trim())?;
+        if main == Some(shortcut) {
+            return Err(format!(
+                "Shortcut \"{}\" is already used as the main shortcut",
+                spec
+            ));
         }
-        SampleFormat::U16 => {
-            let samples = samples.clone();
-            let window = window.clone();
-            device.build_input_stream(
-                &config,
-                move |data: &[u16], _: &_| {
-                    process_input_u16(data, channels, &samples, &window);
-                },
-                log_stream_error,
-                None,
-            )
+        if parsed.iter().any(|(existing, _)| *existing == shortcut) {
+            return Err(format!(
+                "Shortcut \"{}\" is bound to more than one action",
+                spec
+            ));
         }
-        _ => return Err("Unsupported sample format".to_string()),
+        parsed.push((shortcut, *action));
     }
-    .map_err(|e| e.to_string())?;
-
-    stream.play().map_err(|e| e.to_string())?;
+    Ok(parsed)
+}
 
-    *stream_guard = Some(CpalStreamWrapper(stream));
-    println!("Recording started successfully");
-    Ok(())
+#[tauri::command]
+fn get_action_shortcuts(app: AppHandle) -> Result<BTreeMap<String, ShortcutAction>, String> {
+    Ok(load_settings(&app)?.action_shortcuts)
 }
 
 #[tauri::command]
-fn stop_recording(state: State<RecordingState>) -> Result<String, String> {
-    println!("Stopping recording");
-    let mut stream_guard = state.stream.lock().map_err(|e| e.to_string())?;
-    if stream_guard.is_none() {
-        return Err("Recording is not running".to_string());
-    }
-    *stream_guard = None;
-    drop(stream_guard);
+fn set_action_shortcuts(
+    app: AppHandle,
+    shortcuts: BTreeMap<String, ShortcutAction>,
+) -> Result<(), String> {
+    let mut settings = load_settings(&app)?;
+    let old = parse_action_shortcuts(&settings).unwrap_or_default();
+    settings.action_shortcuts = shortcuts;
+    let new = parse_action_shortcuts(&settings)?;
 
-    let sample_rate = {
-        let guard = state.sample_rate.lock().map_err(|e| e.to_string())?;
-        if *guard == 0 {
-            44_100
-        } else {
-            *guard
+    let global_shortcut = app.global_shortcut();
+    for (shortcut, _) in &old {
+        if global_shortcut.is_registered(*shortcut) {
+            global_shortcut
+                .unregister(*shortcut)
+                .map_err(|e| e.to_string())?;
         }
-    };
+    }
+    for (index, (shortcut, _)) in new.iter().enumerate() {
+        if let Err(e) = global_shortcut.register(*shortcut) {
+            // Roll back to the previous bindings if any new one is taken.
+            for (registered, _) in &new[..index] {
+                let _ = global_shortcut.unregister(*registered);
+            }
+            for (previous, _) in &old {
+                let _ = global_shortcut.register(*previous);
+            }
+            return Err(format!(
+                "Failed to register shortcut \"{}\": {}",
+                shortcut.into_string(),
+                e
+            ));
+        }
+    }
 
-    let samples = {
-        let mut guard = state.samples.lock().map_err(|e| e.to_string())?;
-        let data = guard.clone();
-        guard.clear();
-        data
-    };
+    save_settings(&app, &settings)
+}
 
-    let timestamp = SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .map_err(|e| e.to_string())?
-        .as_millis();
-    let file_path = std::env::temp_dir().join(format!("event_searcher_recording_{timestamp}.wav"));
+/// Dispatches a global shortcut event: the main shortcut follows the
+/// configured `ShortcutMode`, action shortcuts fire their action on press.
+fn handle_shortcut(app: &AppHandle, shortcut: &Shortcut, pressed: bool) {
+    let settings = load_settings(app).unwrap_or_default();
+    let action = parse_action_shortcuts(&settings)
+        .unwrap_or_default()
+        .into_iter()
+        .find(|(bound, _)| bound == shortcut)
+        .map(|(_, action)| action);
 
-    let spec = hound::WavSpec {
-        channels: 1,
-        sample_rate,
-        bits_per_sample: 16,
-        sample_format: hound::SampleFormat::Int,
+    let Some(action) = action else {
+        match settings.shortcut_mode {
+            ShortcutMode::Toggle => {
+                if pressed {
+                    show_main_window(app);
+                }
+            }
+            ShortcutMode::PushToTalk => handle_push_to_talk(app, pressed),
+        }
+        return;
     };
-
-    let mut writer = hound::WavWriter::create(&file_path, spec).map_err(|e| e.to_string())?;
-    for sample in samples {
-        writer.write_sample(sample).map_err(|e| e.to_string())?;
+    if !pressed {
+        return;
     }
-    writer.finalize().map_err(|e| e.to_string())?;
 
-    Ok(file_path.to_string_lossy().to_string())
+    let state = app.state::<RecordingState>();
+    match action {
+        ShortcutAction::Start => start_background_recording(app),
+        ShortcutAction::Stop => stop_background_recording(app),
+        ShortcutAction::Cancel => {
+            if let Err(e) = cancel_capture(&state, app) {
+                error!("failed to cancel recording: {}", e);
+            }
+        }
+        ShortcutAction::Toggle => {
+            let recording = state.stream.lock_or_recover().is_some();
+            if recording {
+                stop_background_recording(app);
+            } else {
+                start_background_recording(app);
+            }
+        }
+    }
 }
 
 #[tauri::command]
-fn cancel_recording(state: State<RecordingState>) -> Result<(), String> {
-    println!("Canceling recording");
-    let mut stream_guard = state.stream.lock().map_err(|e| e.to_string())?;
-    *stream_guard = None;
+fn get_shortcut_mode(app: AppHandle) -> Result<ShortcutMode, String> {
+    Ok(load_settings(&app)?.shortcut_mode)
+}
 
-    let mut samples_guard = state.samples.lock().map_err(|e| e.to_string())?;
-    samples_guard.clear();
+#[tauri::command]
+fn set_shortcut_mode(app: AppHandle, mode: ShortcutMode) -> Result<(), String> {
+    let mut settings = load_settings(&app)?;
+    settings.shortcut_mode = mode;
+    save_settings(&app, &settings)
+}
 
-    Ok(())
+fn show_main_window(app: &AppHandle) {
+    if let Some(win) = app.get_webview_window("main") {
+        if let Err(e) = win.show() {
+            error!("failed to show window: {:?}", e);
+        }
+        if let Err(e) = win.set_focus() {
+            error!("failed to focus window: {:?}", e);
+        }
+        // Emit event to frontend to start recording
+        if let Err(e) = win.emit("start-recording", ()) {
+            error!("failed to emit start-recording: {:?}", e);
+        }
+    }
 }
 
-fn location_file_path(app: &AppHandle) -> Result<PathBuf, String> {
-    let dir = app.path().app_config_dir().map_err(|e| e.to_string())?;
-    fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
-    Ok(dir.join("location.json"))
+/// Push-to-talk runs entirely through the `AppHandle`, so the release still
+/// stops the recording even if the window was hidden while the key was held.
+fn handle_push_to_talk(app: &AppHandle, pressed: bool) {
+    if pressed {
+        start_background_recording(app);
+    } else {
+        stop_background_recording(app);
+    }
 }
 
-#[tauri::command]
-fn get_saved_location(app: AppHandle) -> Result<Option<LocationSettings>, String> {
-    let path = location_file_path(&app)?;
-    if !path.exists() {
-        return Ok(None);
+/// Starts a recording with default options from outside the frontend (push-to-
+/// talk, tray menu).
+fn start_background_recording(app: &AppHandle) {
+    let state = app.state::<RecordingState>();
+    let options = load_recording_config(app).start_options();
+    if let Err(e) = start_capture(&state, app, options) {
+        error!("failed to start recording: {}", e);
+        resume_pre_roll(&state, app);
     }
-    let contents = fs::read_to_string(path).map_err(|e| e.to_string())?;
-    let settings: LocationSettings = serde_json::from_str(&contents).map_err(|e| e.to_string())?;
-    Ok(Some(settings))
 }
 
-#[tauri::command]
-fn set_saved_location(app: AppHandle, location: LocationSettings) -> Result<(), String> {
-    let path = location_file_path(&app)?;
-    let data = serde_json::to_string_pretty(&location).map_err(|e| e.to_string())?;
-    fs::write(path, data).map_err(|e| e.to_string())?;
-    Ok(())
+/// Counterpart of `start_background_recording`; announces the saved file with
+/// a `recording-saved` event.
+fn stop_background_recording(app: &AppHandle) {
+    let state = app.state::<RecordingState>();
+    match finish_recording(&state, app, saved_stop_options(app)) {
+        Ok(saved) => {
+            if let Err(e) = app.emit("recording-saved", saved.path) {
+                error!("failed to emit recording-saved: {:?}", e);
+            }
+        }
+        Err(e) => error!("failed to stop recording: {}", e),
+    }
+    resume_pre_roll(&state, app);
 }
 
 #[tauri::command]
@@ -331,58 +4731,268 @@ fn greet(name: &str) -> String {
     format!("Hello, {}! You've been greeted from Rust!", name)
 }
 
+/// Logs to stderr, filtered by `RUST_LOG` (default `info`). Closing a command
+/// span logs how long it took.
+fn init_logging() {
+    use tracing_subscriber::fmt::format::FmtSpan;
+    use tracing_subscriber::EnvFilter;
+
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"));
+    let result = tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_span_events(FmtSpan::CLOSE)
+        .with_writer(std::io::stderr)
+        .try_init();
+    if let Err(e) = result {
+        eprintln!("failed to initialize logging: {}", e);
+    }
+}
+
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
-    use tauri_plugin_global_shortcut::{
-        Code, GlobalShortcutExt, Modifiers, Shortcut, ShortcutState,
-    };
+    use tauri_plugin_global_shortcut::ShortcutState;
 
-    // Register Alt+E as the global shortcut (SuperWhisper-like behavior)
-    let show_shortcut = Shortcut::new(Some(Modifiers::ALT), Code::KeyE);
+    init_logging();
 
     tauri::Builder::default()
-        .manage(RecordingState {
-            stream: Arc::new(Mutex::new(None)),
-            samples: Arc::new(Mutex::new(Vec::new())),
-            sample_rate: Arc::new(Mutex::new(0)),
-        })
+        .manage(RecordingState::new())
+        .manage(playback::PlaybackState::default())
+        .manage(transcribe::TranscriptionState::default())
+        .manage(import::ImportState::default())
         .plugin(
             tauri_plugin_global_shortcut::Builder::new()
-                .with_handler(move |app, _shortcut, event| {
-                    if event.state() == ShortcutState::Pressed {
-                        if let Some(win) = app.get_webview_window("main") {
-                            if let Err(e) = win.show() {
-                                eprintln!("failed to show window: {:?}", e);
-                            }
-                            if let Err(e) = win.set_focus() {
-                                eprintln!("failed to focus window: {:?}", e);
-                            }
-                            // Emit event to frontend to start recording
-                            if let Err(e) = win.emit("start-recording", ()) {
-                                eprintln!("failed to emit start-recording: {:?}", e);
-                            }
-                        }
-                    }
+                .with_handler(move |app, shortcut, event| {
+                    handle_shortcut(app, shortcut, event.state() == ShortcutState::Pressed);
                 })
                 .build(),
         )
         .plugin(tauri_plugin_opener::init())
-        .setup(move |app| {
-            // register the global shortcut so the handler receives events
-            app.handle()
-                .global_shortcut()
-                .register(show_shortcut.clone())
-                .expect("failed to register global shortcut");
+        .plugin(tauri_plugin_clipboard_manager::init())
+        .setup(|app| {
+            // register the configured global shortcut (Alt+E by default,
+            // SuperWhisper-like behavior) so the handler receives events
+            let settings = load_settings(app.handle()).unwrap_or_default();
+            let shortcut = parse_shortcut(configured_shortcut(&settings)).or_else(|e| {
+                warn!("{}; falling back to {}", e, DEFAULT_SHORTCUT);
+                parse_shortcut(DEFAULT_SHORTCUT)
+            })?;
+            if let Err(e) = app.handle().global_shortcut().register(shortcut) {
+                error!("failed to register global shortcut: {:?}", e);
+            }
+            match parse_action_shortcuts(&settings) {
+                Ok(bindings) => {
+                    for (shortcut, _) in bindings {
+                        if let Err(e) = app.handle().global_shortcut().register(shortcut) {
+                            error!("failed to register action shortcut: {:?}", e);
+                        }
+                    }
+                }
+                Err(e) => error!("ignoring action shortcuts: {}", e),
+            }
+            let saved_gain = load_recording_config(app.handle())
+                .gain
+                .filter(|gain| gain.is_finite() && *gain >= 0.0);
+            if let Some(gain) = saved_gain {
+                *app.state::<RecordingState>().gain.lock_or_recover() = gain;
+            }
+            tray::setup_tray(app.handle())?;
+            recovery::announce_recovery_files(app.handle());
+            let handle = app.handle().clone();
+            std::thread::spawn(move || {
+                if let Err(e) = recordings::prune_recordings(handle) {
+                    error!("failed to prune old recordings: {}", e);
+                }
+            });
             Ok(())
         })
         .invoke_handler(tauri::generate_handler![
             greet,
+            list_input_devices,
+            get_supported_bit_depths,
+            refresh_devices,
+            list_loopback_devices,
+            get_input_device_config,
+            get_recording_config,
+            set_recording_config,
             start_recording,
             stop_recording,
+            start_monitoring,
+            stop_monitoring,
+            get_pre_roll,
+            set_pre_roll,
+            set_pre_roll_armed,
+            get_quality_preset,
+            set_quality_preset,
+            get_recording_duration,
+            get_stream_info,
+            is_recording,
+            snapshot_recording,
+            get_last_samples,
+            pause_recording,
+            resume_recording,
+            set_mute,
             cancel_recording,
+            undo_cancel,
+            mic_test,
+            start_recording_with_delay,
+            get_input_gain,
+            set_input_gain,
+            get_highpass_cutoff,
+            set_highpass_cutoff,
+            get_level_interval,
+            set_level_interval,
+            get_agc,
+            set_agc_enabled,
+            set_agc_target,
+            get_noise_gate,
+            set_noise_gate,
+            get_device_settings,
+            set_device_settings,
+            get_limiter,
+            set_limiter_enabled,
+            set_limiter_params,
+            get_meter_ballistics,
+            set_meter_ballistics,
+            get_level_thresholds,
+            set_level_thresholds,
+            get_silence_detection,
+            set_silence_detection,
             get_saved_location,
-            set_saved_location
+            set_saved_location,
+            detect_location,
+            get_recordings_dir,
+            get_default_recordings_path,
+            set_recordings_dir,
+            get_shortcut,
+            set_shortcut,
+            get_shortcut_mode,
+            set_shortcut_mode,
+            get_action_shortcuts,
+            set_action_shortcuts,
+            recordings::list_recordings,
+            recordings::delete_recording,
+            recordings::rename_recording,
+            recordings::set_recording_tags,
+            recordings::trim_silence,
+            recordings::concat_recordings,
+            recordings::split_channels,
+            recordings::merge_to_stereo,
+            recordings::export_ulaw,
+            recordings::verify_recording,
+            recordings::prune_recordings,
+            recordings::set_recording_keep,
+            import::import_audio,
+            import::cancel_import,
+            recordings::reveal_recording,
+            recordings::normalize_recordings_to_lufs,
+            recordings::analyze_recording,
+            recovery::list_recovery_files,
+            playback::play_recording,
+            playback::stop_playback,
+            paste::paste_text,
+            permission::check_microphone_permission,
+            permission::request_microphone_permission,
+            transcribe::transcribe_file,
+            transcribe::cancel_transcription,
+            transcribe::cancel_transcribe_all,
+            transcribe::transcribe_all,
+            transcribe::start_live_transcription,
+            transcribe::prepare_for_transcription,
+            upload::upload_recording
         ])
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .build(tauri::generate_context!())
+        .expect("error while building tauri application")
+        .run(|app, event| {
+            if let RunEvent::Exit = event {
+                save_recording_on_exit(app);
+            }
+        });
+}
+
+/// Called as the app exits, e.g. when the last window is closed mid-recording.
+/// The recovery copy is brought up to date first and only removed once the
+/// normal stop path has saved the recording.
+fn save_recording_on_exit(app: &AppHandle) {
+    let state = app.state::<RecordingState>();
+    if state.stream.lock_or_recover().is_none() {
+        return;
+    }
+    info!("Exiting during a recording; saving it");
+
+    if let Err(e) = recovery::checkpoint(&state) {
+        error!("failed to update recovery copy: {}", e);
+    }
+    match finish_recording(&state, app, saved_stop_options(app)) {
+        Ok(saved) => info!(path = %saved.path, "Saved recording on exit"),
+        Err(e) => match recovery::recovery_path(&state) {
+            Some(path) => error!(
+                "failed to save recording on exit: {}; recovery copy kept at {}",
+                e,
+                path.display()
+            ),
+            None => error!("failed to save recording on exit: {}", e),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Panics on another thread while holding `mutex`, leaving it poisoned.
+    fn poison<T: Send + 'static>(mutex: Arc<Mutex<T>>) {
+        let result = std::thread::spawn(move || {
+            let _guard = mutex.lock().unwrap();
+            panic!("poisoning the lock on purpose");
+        })
+        .join();
+        assert!(result.is_err());
+    }
+
+    fn test_capture() -> CaptureSettings {
+        CaptureSettings {
+            channels: 2,
+            sample_rate: 48_000,
+            channel_mode: ChannelMode::Mono,
+            max_samples: None,
+            waveform_bucket_frames: 480,
+            vad: None,
+            segment_samples: None,
+            bit_depth: 16,
+            monitor_only: false,
+            channel_select: None,
+            pre_roll_samples: 0,
+            pcm_events: false,
+        }
+    }
+
+    #[test]
+    fn capture_restarts_after_a_poisoned_lock() {
+        let state = RecordingState::new();
+        state.samples.lock_or_recover().extend([1, 2, 3]);
+        poison(state.stream.clone());
+        poison(state.samples.clone());
+        poison(state.sample_rate.clone());
+        poison(state.writer.clone());
+        poison(state.cancelled.clone());
+        assert!(state.samples.is_poisoned());
+
+        // `start_capture` up to the stream build, minus the device itself.
+        let (stream_guard, bit_depth) = begin_capture(&state, &StartOptions::default())
+            .unwrap()
+            .expect("no recording is running");
+        assert!(stream_guard.is_none());
+        assert_eq!(bit_depth, 16);
+        reset_capture_state(&state, &test_capture(), "test input".to_string(), None);
+        assert!(state.writer.lock_or_recover().is_none());
+        discard_cancelled_recording(&state.cancelled);
+        drop(stream_guard);
+
+        assert!(state.samples.lock_or_recover().is_empty());
+        assert_eq!(*state.sample_rate.lock_or_recover(), 48_000);
+        assert_eq!(
+            state.device_name.lock_or_recover().as_deref(),
+            Some("test input")
+        );
+    }
 }
@@ -0,0 +1,56 @@
+//! Hands transcribed text to whatever app had focus before the recorder.
+
+use enigo::{Direction, Enigo, Key, Keyboard, Settings};
+use std::time::Duration;
+use tauri::{AppHandle, Manager};
+use tauri_plugin_clipboard_manager::ClipboardExt;
+
+/// How long to wait after hiding the window before pasting. Without it the
+/// keystroke can land before focus has returned to the previous app.
+const DEFAULT_PASTE_DELAY_MS: u64 = 150;
+
+/// Copies `text` to the clipboard and, unless `paste` is false, hides the
+/// window and sends the platform paste shortcut to the newly focused app.
+#[tauri::command]
+pub async fn paste_text(
+    app: AppHandle,
+    text: String,
+    paste: Option<bool>,
+    delay_ms: Option<u64>,
+) -> Result<(), String> {
+    app.clipboard()
+        .write_text(text)
+        .map_err(|e| format!("Failed to write to the clipboard: {}", e))?;
+    if !paste.unwrap_or(true) {
+        return Ok(());
+    }
+
+    if let Some(win) = app.get_webview_window("main") {
+        win.hide().map_err(|e| e.to_string())?;
+    }
+    let delay = Duration::from_millis(delay_ms.unwrap_or(DEFAULT_PASTE_DELAY_MS));
+    tauri::async_runtime::spawn_blocking(move || {
+        std::thread::sleep(delay);
+        send_paste_keystroke()
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+fn send_paste_keystroke() -> Result<(), String> {
+    #[cfg(target_os = "macos")]
+    let modifier = Key::Meta;
+    #[cfg(not(target_os = "macos"))]
+    let modifier = Key::Control;
+
+    let mut enigo =
+        Enigo::new(&Settings::default()).map_err(|e| format!("Failed to simulate paste: {}", e))?;
+    let result = enigo
+        .key(modifier, Direction::Press)
+        .and_then(|_| enigo.key(Key::Unicode('v'), Direction::Click));
+    // Always release the modifier, even if the click failed.
+    let release = enigo.key(modifier, Direction::Release);
+    result
+        .and(release)
+        .map_err(|e| format!("Failed to simulate paste: {}", e))
+}
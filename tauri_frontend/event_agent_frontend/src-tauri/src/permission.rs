@@ -0,0 +1,109 @@
+//! Microphone access. macOS gates the microphone behind a per-app privacy
+//! permission, and until it is granted the input stream opens fine but only
+//! delivers silence. Other platforms have no such check and always report
+//! access as granted.
+
+use tauri::{AppHandle, Emitter};
+use tracing::{info, warn};
+
+pub const MICROPHONE_PERMISSION_DENIED: &str = "microphone permission denied";
+
+// Only macOS ever reports anything but `Granted`.
+#[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+#[derive(serde::Serialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum PermissionStatus {
+    Granted,
+    Denied,
+    /// Blocked by a device management policy; the user can't change it.
+    Restricted,
+    /// The user hasn't been asked yet; see `request_microphone_permission`.
+    NotDetermined,
+}
+
+#[tauri::command]
+pub fn check_microphone_permission() -> PermissionStatus {
+    platform::microphone_status()
+}
+
+/// Shows the system prompt if the user hasn't been asked yet and returns the
+/// resulting status. Once the user has answered, macOS no longer prompts and
+/// this just returns the current status.
+#[tauri::command]
+pub async fn request_microphone_permission() -> Result<PermissionStatus, String> {
+    let status = tauri::async_runtime::spawn_blocking(platform::request_microphone_access)
+        .await
+        .map_err(|e| e.to_string())?;
+    info!(?status, "Microphone permission requested");
+    Ok(status)
+}
+
+/// Fails with `MICROPHONE_PERMISSION_DENIED` unless microphone access is
+/// granted, and emits `permission-required` with the status so the UI can
+/// either request access or point the user to System Settings.
+pub fn ensure_microphone_access(app: &AppHandle) -> Result<(), String> {
+    let status = platform::microphone_status();
+    if status == PermissionStatus::Granted {
+        return Ok(());
+    }
+    warn!(?status, "Microphone access is blocked");
+    let _ = app.emit("permission-required", status);
+    Err(MICROPHONE_PERMISSION_DENIED.to_string())
+}
+
+#[cfg(target_os = "macos")]
+mod platform {
+    use super::PermissionStatus;
+    use block2::RcBlock;
+    use objc2::runtime::Bool;
+    use objc2_av_foundation::{AVAuthorizationStatus, AVCaptureDevice, AVMediaTypeAudio};
+    use std::sync::mpsc;
+
+    pub fn microphone_status() -> PermissionStatus {
+        let Some(media_type) = (unsafe { AVMediaTypeAudio }) else {
+            return PermissionStatus::Granted;
+        };
+        match unsafe { AVCaptureDevice::authorizationStatusForMediaType(media_type) } {
+            AVAuthorizationStatus::Authorized => PermissionStatus::Granted,
+            AVAuthorizationStatus::Denied => PermissionStatus::Denied,
+            AVAuthorizationStatus::Restricted => PermissionStatus::Restricted,
+            _ => PermissionStatus::NotDetermined,
+        }
+    }
+
+    /// Blocks until the user answers the prompt.
+    pub fn request_microphone_access() -> PermissionStatus {
+        let Some(media_type) = (unsafe { AVMediaTypeAudio }) else {
+            return PermissionStatus::Granted;
+        };
+        if microphone_status() != PermissionStatus::NotDetermined {
+            return microphone_status();
+        }
+        let (sender, receiver) = mpsc::channel();
+        // The handler runs on an arbitrary dispatch queue.
+        let handler = RcBlock::new(move |granted: Bool| {
+            let _ = sender.send(granted.as_bool());
+        });
+        unsafe {
+            AVCaptureDevice::requestAccessForMediaType_completionHandler(media_type, &handler);
+        }
+        match receiver.recv() {
+            Ok(true) => PermissionStatus::Granted,
+            Ok(false) => PermissionStatus::Denied,
+            Err(_) => microphone_status(),
+        }
+    }
+}
+
+#[cfg(not(target_os = "macos"))]
+mod platform {
+    use super::PermissionStatus;
+
+    pub fn microphone_status() -> PermissionStatus {
+        PermissionStatus::Granted
+    }
+
+    pub fn request_microphone_access() -> PermissionStatus {
+        PermissionStatus::Granted
+    }
+}
@@ -0,0 +1,159 @@
+//! Playback of saved recordings through the default output device.
+
+use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use cpal::SampleFormat;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use tauri::{AppHandle, Emitter, Manager, State};
+use tracing::error;
+
+use crate::{audio, recordings, CpalStreamWrapper, MutexExt};
+
+#[derive(Clone, Default)]
+pub struct PlaybackState {
+    stream: Arc<Mutex<Option<CpalStreamWrapper>>>,
+}
+
+/// Interleaved audio already converted to the output device's rate and
+/// channel count, plus the read position shared with the audio callback.
+struct PlaybackBuffer {
+    samples: Vec<f32>,
+    position: AtomicUsize,
+    finished: AtomicBool,
+}
+
+/// Plays a WAV recording, replacing any playback already in progress.
+/// Emits `playback-finished` with the path once the last sample was played.
+/// Decoding and resampling run on the blocking pool.
+#[tauri::command]
+pub async fn play_recording(app: AppHandle, path: String) -> Result<(), String> {
+    let path = recordings::resolve_recording_path(&app, &path)?;
+    tauri::async_runtime::spawn_blocking(move || start_playback(&app, &path))
+        .await
+        .map_err(|e| e.to_string())?
+}
+
+fn start_playback(app: &AppHandle, path: &Path) -> Result<(), String> {
+    let state = app.state::<PlaybackState>();
+    *state.stream.lock_or_recover() = None;
+
+    let wav = audio::read_wav(path)?;
+    let path = path.to_string_lossy().to_string();
+    let host = cpal::default_host();
+    let device = host
+        .default_output_device()
+        .ok_or("No output device available")?;
+    let supported_config = device.default_output_config().map_err(|e| e.to_string())?;
+    let sample_format = supported_config.sample_format();
+    let config: cpal::StreamConfig = supported_config.into();
+    if config.channels == 0 {
+        return Err("output device reported zero channels".to_string());
+    }
+
+    let buffer = Arc::new(PlaybackBuffer {
+        samples: convert_for_output(&wav, config.sample_rate.0, config.channels),
+        position: AtomicUsize::new(0),
+        finished: AtomicBool::new(false),
+    });
+
+    let stream = match sample_format {
+        SampleFormat::F32 => build_output::<f32>(&device, &config, &buffer, app, &path, |s| s),
+        SampleFormat::I16 => build_output::<i16>(&device, &config, &buffer, app, &path, |s| {
+            (s * i16::MAX as f32) as i16
+        }),
+        SampleFormat::U16 => build_output::<u16>(&device, &config, &buffer, app, &path, |s| {
+            ((s + 1.0) * 0.5 * u16::MAX as f32) as u16
+        }),
+        other => return Err(format!("Unsupported output sample format: {:?}", other)),
+    }
+    .map_err(|e| e.to_string())?;
+
+    stream.play().map_err(|e| e.to_string())?;
+    *state.stream.lock_or_recover() = Some(CpalStreamWrapper(stream));
+    Ok(())
+}
+
+#[tauri::command]
+pub fn stop_playback(state: State<PlaybackState>) -> Result<(), String> {
+    *state.stream.lock_or_recover() = None;
+    Ok(())
+}
+
+fn build_output<T: cpal::SizedSample + Send + 'static>(
+    device: &cpal::Device,
+    config: &cpal::StreamConfig,
+    buffer: &Arc<PlaybackBuffer>,
+    app: &AppHandle,
+    path: &str,
+    convert: impl Fn(f32) -> T + Send + 'static,
+) -> Result<cpal::Stream, cpal::BuildStreamError> {
+    let buffer = buffer.clone();
+    let app = app.clone();
+    let path = path.to_string();
+    let silence = convert(0.0);
+    device.build_output_stream(
+        config,
+        move |data: &mut [T], _: &_| {
+            let start = buffer.position.load(Ordering::Relaxed);
+            let remaining = buffer.samples.get(start..).unwrap_or_default();
+            for (index, out) in data.iter_mut().enumerate() {
+                *out = remaining
+                    .get(index)
+                    .map(|&sample| convert(sample.clamp(-1.0, 1.0)))
+                    .unwrap_or(silence);
+            }
+            buffer
+                .position
+                .store(start + data.len().min(remaining.len()), Ordering::Relaxed);
+
+            if data.len() >= remaining.len() && !buffer.finished.swap(true, Ordering::SeqCst) {
+                // Dropping the stream must not happen on the audio thread.
+                let app = app.clone();
+                let path = path.clone();
+                std::thread::spawn(move || {
+                    *app.state::<PlaybackState>().stream.lock_or_recover() = None;
+                    if let Err(e) = app.emit("playback-finished", path) {
+                        error!("failed to emit playback-finished: {:?}", e);
+                    }
+                });
+            }
+        },
+        |err| error!("an error occurred on playback stream: {}", err),
+        None,
+    )
+}
+
+/// Resamples and remaps the file's channels to the output layout. Mono files
+/// are duplicated to every output channel; multichannel files are downmixed
+/// for a mono output and otherwise truncated or padded with their last channel.
+fn convert_for_output(wav: &audio::WavAudio, sample_rate: u32, channels: u16) -> Vec<f32> {
+    let file_channels = wav.channels.max(1) as usize;
+    let planes: Vec<Vec<f32>> = if channels == 1 {
+        vec![audio::downmix_to_mono(&wav.samples, wav.channels)]
+    } else {
+        (0..file_channels)
+            .map(|channel| {
+                wav.samples
+                    .iter()
+                    .skip(channel)
+                    .step_by(file_channels)
+                    .copied()
+                    .collect()
+            })
+            .collect()
+    };
+    let planes: Vec<Vec<f32>> = planes
+        .iter()
+        .map(|plane| audio::resample_linear(plane, wav.sample_rate, sample_rate))
+        .collect();
+
+    let frames = planes.iter().map(Vec::len).min().unwrap_or(0);
+    let last_plane = planes.len() - 1;
+    (0..frames)
+        .flat_map(|frame| {
+            let planes = &planes;
+            (0..channels as usize).map(move |channel| planes[channel.min(last_plane)][frame])
+        })
+        .collect()
+}
@@ -0,0 +1,989 @@
+//! History of recordings saved in the recordings directory.
+
+use std::cmp::Reverse;
+use std::fs;
+use std::io::{BufReader, ErrorKind};
+use std::path::{Path, PathBuf};
+use tauri::AppHandle;
+use tracing::{info, warn};
+
+use crate::{
+    audio, create_wav_writer, duration_ms, filename, load_recording_config, read_wav_samples,
+    recording_loudness, recordings_dir, recovery, sidecar_path, to_i16, transcribe, unix_millis,
+    write_audio_file, write_sidecar, FileWavWriter, OutputFormat, RecordingMetadata,
+    RECORDING_FILE_PREFIX,
+};
+
+/// Audio kept before the first and after the last loud frame by
+/// `trim_silence`, so word onsets and tails aren't clipped.
+const TRIM_PADDING_MS: u64 = 100;
+
+/// Recordings within this many LU of the target are left alone by
+/// `normalize_recordings_to_lufs`.
+const LOUDNESS_TOLERANCE_LU: f32 = 0.5;
+/// Ceiling and release of the limiter that catches peaks pushed over full
+/// scale by loudness normalization.
+const LOUDNESS_CEILING_DBFS: f32 = -1.0;
+const LOUDNESS_LIMITER_RELEASE_MS: f32 = 150.0;
+
+const MILLIS_PER_DAY: u64 = 24 * 60 * 60 * 1000;
+
+#[derive(serde::Serialize, Clone)]
+pub struct RecordingEntry {
+    path: String,
+    /// Milliseconds since the Unix epoch, parsed from the file name.
+    created_at: u64,
+    duration_ms: u64,
+    tags: Vec<String>,
+    note: Option<String>,
+    metadata: Option<RecordingMetadata>,
+}
+
+/// Lists saved recordings, newest first.
+#[tauri::command]
+pub fn list_recordings(app: AppHandle) -> Result<Vec<RecordingEntry>, String> {
+    let dir = recordings_dir(&app)?;
+    let entries = match fs::read_dir(&dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(format!("Failed to read {}: {}", dir.display(), e)),
+    };
+
+    let mut recordings = Vec::new();
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let metadata = read_sidecar(&path);
+        // Recordings named from a filename template carry their timestamp in
+        // the sidecar only.
+        let created_at = match (recording_timestamp(&path), &metadata) {
+            (Some(created_at), _) => created_at,
+            (None, Some(metadata)) if is_audio_file(&path) => metadata.created_at,
+            _ => continue,
+        };
+        let duration_ms = match &metadata {
+            Some(metadata) => metadata.duration_ms,
+            None => wav_duration_ms(&path).unwrap_or(0),
+        };
+        let (tags, note) = match &metadata {
+            Some(metadata) => (metadata.tags.clone(), metadata.note.clone()),
+            None => (Vec::new(), None),
+        };
+        recordings.push(RecordingEntry {
+            path: path.to_string_lossy().to_string(),
+            created_at,
+            duration_ms,
+            tags,
+            note,
+            metadata,
+        });
+    }
+
+    recordings.sort_by_key(|entry| Reverse(entry.created_at));
+    Ok(recordings)
+}
+
+/// Paths of the saved recordings, newest first, as `list_recordings` finds
+/// them.
+pub fn recording_paths(app: &AppHandle) -> Result<Vec<PathBuf>, String> {
+    Ok(list_recordings(app.clone())?
+        .into_iter()
+        .map(|entry| PathBuf::from(entry.path))
+        .collect())
+}
+
+/// Deletes a recording and its sidecar.
+#[tauri::command]
+pub fn delete_recording(app: AppHandle, path: String) -> Result<(), String> {
+    let path = resolve_recording_path(&app, &path)?;
+    if !path.is_file() || !is_audio_file(&path) {
+        return Err(format!("{} is not a recording file", path.display()));
+    }
+    remove_recording_files(&path)
+}
+
+/// Deletes recordings older than `RecordingConfig::retention_days`, with
+/// their sidecars, and returns their paths. Recordings marked to keep are
+/// left alone, and nothing is deleted while no retention is configured. Runs
+/// at startup too.
+#[tauri::command]
+pub fn prune_recordings(app: AppHandle) -> Result<Vec<String>, String> {
+    let Some(days) = load_recording_config(&app).retention_days else {
+        return Ok(Vec::new());
+    };
+    let cutoff = unix_millis()?.saturating_sub(days as u64 * MILLIS_PER_DAY);
+
+    let mut deleted = Vec::new();
+    for entry in list_recordings(app.clone())? {
+        let keep = entry
+            .metadata
+            .as_ref()
+            .is_some_and(|metadata| metadata.keep);
+        if entry.created_at >= cutoff || keep {
+            continue;
+        }
+        let removed = resolve_recording_path(&app, &entry.path)
+            .and_then(|path| remove_recording_files(&path));
+        match removed {
+            Ok(()) => deleted.push(entry.path),
+            Err(e) => warn!("failed to prune {}: {}", entry.path, e),
+        }
+    }
+    if !deleted.is_empty() {
+        info!(
+            "Pruned {} recordings older than {} days",
+            deleted.len(),
+            days
+        );
+    }
+    Ok(deleted)
+}
+
+/// Marks a recording to be kept, or no longer kept, by `prune_recordings`.
+#[tauri::command]
+pub fn set_recording_keep(
+    app: AppHandle,
+    path: String,
+    keep: bool,
+) -> Result<RecordingMetadata, String> {
+    let path = resolve_recording_path(&app, &path)?;
+    let mut metadata = read_sidecar(&path)
+        .ok_or_else(|| format!("{} has no readable metadata sidecar", path.display()))?;
+    metadata.keep = keep;
+    write_sidecar(&path, &metadata)?;
+    Ok(metadata)
+}
+
+fn remove_recording_files(path: &Path) -> Result<(), String> {
+    fs::remove_file(path).map_err(|e| format!("Failed to delete {}: {}", path.display(), e))?;
+    for companion in companion_paths(path) {
+        match fs::remove_file(&companion) {
+            Ok(()) => {}
+            Err(e) if e.kind() == ErrorKind::NotFound => {}
+            Err(e) => return Err(format!("Failed to delete {}: {}", companion.display(), e)),
+        }
+    }
+    Ok(())
+}
+
+/// Files that belong to a recording and follow it when it is renamed or
+/// deleted: the metadata sidecar and the transcripts.
+fn companion_paths(path: &Path) -> [PathBuf; 3] {
+    [
+        sidecar_path(path),
+        transcribe::transcript_path(path),
+        transcribe::transcript_json_path(path),
+    ]
+}
+
+/// Opens the system file manager with the recording selected. `path` must
+/// exist inside the recordings directory.
+#[tauri::command]
+pub fn reveal_recording(app: AppHandle, path: String) -> Result<(), String> {
+    let path = resolve_recording_path(&app, &path)?;
+    tauri_plugin_opener::reveal_item_in_dir(&path)
+        .map_err(|e| format!("Failed to reveal {}: {}", path.display(), e))
+}
+
+/// Renames a recording and its sidecar, keeping the extension, and returns the
+/// new path. `new_name` is sanitized into a stem in the same directory and
+/// must not collide with an existing recording or sidecar.
+#[tauri::command]
+pub fn rename_recording(
+    app: AppHandle,
+    old_path: String,
+    new_name: String,
+) -> Result<String, String> {
+    let old_path = resolve_recording_path(&app, &old_path)?;
+    if !old_path.is_file() || !is_audio_file(&old_path) {
+        return Err(format!("{} is not a recording file", old_path.display()));
+    }
+    let old_sidecar = sidecar_path(&old_path);
+    // History entries without the timestamped prefix are only listed through
+    // their sidecar's creation time.
+    if !old_sidecar.exists() {
+        return Err(format!(
+            "{} has no metadata sidecar; renaming it would drop it from the history",
+            old_path.display()
+        ));
+    }
+
+    let stem = filename::sanitize_name(&new_name)?;
+    let extension = old_path
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .unwrap_or_default();
+    let new_path = old_path.with_file_name(format!("{}.{}", stem, extension));
+    if new_path == old_path {
+        return Ok(new_path.to_string_lossy().to_string());
+    }
+    let new_sidecar = sidecar_path(&new_path);
+    if new_path.exists() || companion_paths(&new_path).iter().any(|path| path.exists()) {
+        return Err(format!("{} already exists", new_path.display()));
+    }
+
+    fs::rename(&old_path, &new_path)
+        .map_err(|e| format!("Failed to rename {}: {}", old_path.display(), e))?;
+    if let Err(e) = fs::rename(&old_sidecar, &new_sidecar) {
+        // Put the audio back so it stays paired with its sidecar.
+        if let Err(e) = fs::rename(&new_path, &old_path) {
+            warn!("failed to restore {}: {}", old_path.display(), e);
+        }
+        return Err(format!("Failed to rename {}: {}", old_sidecar.display(), e));
+    }
+    // A transcript that fails to move is regenerated by `transcribe_all`, so
+    // it doesn't undo the rename.
+    for transcript in [
+        transcribe::transcript_path,
+        transcribe::transcript_json_path,
+    ] {
+        let (old, new) = (transcript(&old_path), transcript(&new_path));
+        if old.exists() {
+            if let Err(e) = fs::rename(&old, &new) {
+                warn!("failed to rename {}: {}", old.display(), e);
+            }
+        }
+    }
+    info!("Renamed {} to {}", old_path.display(), new_path.display());
+    Ok(new_path.to_string_lossy().to_string())
+}
+
+#[derive(serde::Serialize, Clone)]
+pub struct TrimResult {
+    path: String,
+    /// False when the whole file was below the threshold and was left as is.
+    trimmed: bool,
+}
+
+/// Cuts leading and trailing audio below `threshold_dbfs` from a WAV
+/// recording, in place, keeping `TRIM_PADDING_MS` on each side. The sidecar's
+/// duration is updated to match.
+#[tauri::command]
+pub fn trim_silence(
+    app: AppHandle,
+    path: String,
+    threshold_dbfs: f32,
+) -> Result<TrimResult, String> {
+    if !threshold_dbfs.is_finite() || threshold_dbfs > 0.0 {
+        return Err(format!(
+            "Invalid silence threshold: {} dBFS",
+            threshold_dbfs
+        ));
+    }
+    let path = resolve_recording_path(&app, &path)?;
+    if OutputFormat::from_extension(path.extension().and_then(|ext| ext.to_str()).unwrap_or(""))
+        != Some(OutputFormat::Wav)
+    {
+        return Err(format!("{} is not a WAV recording", path.display()));
+    }
+
+    let spec = hound::WavReader::open(&path)
+        .map_err(|e| format!("Failed to open {}: {}", path.display(), e))?
+        .spec();
+    let samples = read_wav_samples(&path)?;
+    let padding_frames = (spec.sample_rate as u64 * TRIM_PADDING_MS / 1000) as usize;
+    let untouched = TrimResult {
+        path: path.to_string_lossy().to_string(),
+        trimmed: false,
+    };
+    let Some(range) =
+        audio::non_silent_range(&samples, spec.channels, threshold_dbfs, padding_frames)
+    else {
+        return Ok(untouched);
+    };
+    if range.len() == samples.len() {
+        return Ok(untouched);
+    }
+
+    let trimmed = &samples[range];
+    write_audio_file(
+        &path,
+        trimmed,
+        spec.sample_rate,
+        spec.channels,
+        OutputFormat::Wav,
+    )?;
+    if let Some(mut metadata) = read_sidecar(&path) {
+        metadata.duration_ms = duration_ms(trimmed.len(), spec.sample_rate, spec.channels);
+        write_sidecar(&path, &metadata)?;
+    }
+    Ok(TrimResult {
+        trimmed: true,
+        ..untouched
+    })
+}
+
+/// Brings every 16-bit WAV recording to `target` LUFS integrated loudness, in
+/// place, and returns the paths that were rewritten. Recordings already within
+/// `LOUDNESS_TOLERANCE_LU`, and ones too short or quiet to measure, are
+/// skipped. A recording that fails to process is logged and skipped rather
+/// than aborting the batch.
+///
+/// Only saved recordings, as listed by `list_recordings`, are touched; other
+/// WAV files in the recordings directory and recovery files are left alone.
+#[tauri::command]
+pub async fn normalize_recordings_to_lufs(
+    app: AppHandle,
+    target: f32,
+) -> Result<Vec<String>, String> {
+    if !target.is_finite() || target >= 0.0 {
+        return Err(format!("Invalid loudness target: {} LUFS", target));
+    }
+    let paths: Vec<PathBuf> = recording_paths(&app)?
+        .into_iter()
+        .filter(|path| {
+            OutputFormat::from_extension(
+                path.extension().and_then(|ext| ext.to_str()).unwrap_or(""),
+            ) == Some(OutputFormat::Wav)
+        })
+        .filter(|path| !recovery::is_recovery_file(path))
+        .collect();
+    tauri::async_runtime::spawn_blocking(move || normalize_all_loudness(&paths, target))
+        .await
+        .map_err(|e| e.to_string())
+}
+
+fn normalize_all_loudness(paths: &[PathBuf], target: f32) -> Vec<String> {
+    let mut modified = Vec::new();
+    for path in paths {
+        match normalize_loudness(path, target) {
+            Ok(true) => modified.push(path.to_string_lossy().to_string()),
+            Ok(false) => {}
+            Err(e) => warn!(
+                "skipping loudness normalization of {}: {}",
+                path.display(),
+                e
+            ),
+        }
+    }
+    info!(
+        "Normalized {} recordings to {} LUFS",
+        modified.len(),
+        target
+    );
+    modified
+}
+
+/// Returns whether the file was rewritten.
+fn normalize_loudness(path: &Path, target: f32) -> Result<bool, String> {
+    let spec = hound::WavReader::open(path)
+        .map_err(|e| format!("Failed to open {}: {}", path.display(), e))?
+        .spec();
+    if spec.bits_per_sample != 16 || spec.sample_format != hound::SampleFormat::Int {
+        return Err(format!(
+            "{}-bit audio is not supported",
+            spec.bits_per_sample
+        ));
+    }
+    let mut metadata = read_sidecar(path);
+    let mid_side = metadata.as_ref().is_some_and(|metadata| metadata.mid_side);
+    let mut samples = read_wav_samples(path)?;
+    let Some(loudness) = recording_loudness(&samples, spec.channels, spec.sample_rate, mid_side)
+    else {
+        return Ok(false);
+    };
+    if (loudness - target).abs() <= LOUDNESS_TOLERANCE_LU {
+        return Ok(false);
+    }
+
+    let limiter = audio::LimiterParams {
+        threshold: audio::dbfs_to_linear(LOUDNESS_CEILING_DBFS),
+        release: audio::smoothing_coefficient(LOUDNESS_LIMITER_RELEASE_MS, spec.sample_rate),
+    };
+    audio::apply_gain_limited(
+        &mut samples,
+        spec.channels,
+        audio::dbfs_to_linear(target - loudness),
+        &limiter,
+    );
+    write_audio_file(
+        path,
+        &samples,
+        spec.sample_rate,
+        spec.channels,
+        OutputFormat::Wav,
+    )?;
+    if let Some(metadata) = metadata.as_mut() {
+        let peak = samples
+            .iter()
+            .map(|&sample| (sample as i32).unsigned_abs())
+            .max()
+            .unwrap_or(0);
+        metadata.peak_dbfs = audio::linear_to_dbfs(peak as f32 / i16::MAX as f32);
+        metadata.integrated_lufs =
+            recording_loudness(&samples, spec.channels, spec.sample_rate, mid_side);
+        write_sidecar(path, metadata)?;
+    }
+    Ok(true)
+}
+
+/// Level statistics for one WAV recording, for spotting clipping, DC bias or
+/// a recording that is too quiet; see `audio::analyze`.
+#[tauri::command]
+pub async fn analyze_recording(app: AppHandle, path: String) -> Result<audio::AudioStats, String> {
+    let path = resolve_recording_path(&app, &path)?;
+    if OutputFormat::from_extension(path.extension().and_then(|ext| ext.to_str()).unwrap_or(""))
+        != Some(OutputFormat::Wav)
+    {
+        return Err(format!("{} is not a WAV recording", path.display()));
+    }
+    tauri::async_runtime::spawn_blocking(move || {
+        let wav = audio::read_wav(&path)?;
+        Ok(audio::analyze(&wav.samples, wav.channels, wav.sample_rate))
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+/// Result of `verify_recording`: what the WAV header promises next to what
+/// the file actually holds.
+#[derive(serde::Serialize, Clone)]
+pub struct WavVerification {
+    sample_rate: u32,
+    channels: u16,
+    bits_per_sample: u16,
+    /// Interleaved samples according to the data chunk header.
+    declared_samples: u64,
+    /// Interleaved samples that could actually be read.
+    actual_samples: u64,
+    declared_duration_ms: u64,
+    actual_duration_ms: u64,
+    /// True when every declared sample was read.
+    complete: bool,
+    /// Why reading stopped early, if it did.
+    error: Option<String>,
+}
+
+/// Reads every sample of a WAV recording to check that the data chunk is as
+/// long as its header says, e.g. to flag files truncated by a sync. Fails only
+/// when the header itself can't be read.
+#[tauri::command]
+pub async fn verify_recording(app: AppHandle, path: String) -> Result<WavVerification, String> {
+    let path = resolve_recording_path(&app, &path)?;
+    tauri::async_runtime::spawn_blocking(move || {
+        let mut reader = hound::WavReader::open(&path)
+            .map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+        let spec = reader.spec();
+        let declared_samples = reader.len() as u64;
+        let (actual_samples, error) = match spec.sample_format {
+            hound::SampleFormat::Float => count_samples::<f32, _>(&mut reader),
+            hound::SampleFormat::Int => count_samples::<i32, _>(&mut reader),
+        };
+        let complete = error.is_none() && actual_samples == declared_samples;
+        if !complete {
+            warn!(
+                "{} holds {} of {} declared samples",
+                path.display(),
+                actual_samples,
+                declared_samples
+            );
+        }
+        Ok(WavVerification {
+            sample_rate: spec.sample_rate,
+            channels: spec.channels,
+            bits_per_sample: spec.bits_per_sample,
+            declared_samples,
+            actual_samples,
+            declared_duration_ms: duration_ms(
+                declared_samples as usize,
+                spec.sample_rate,
+                spec.channels,
+            ),
+            actual_duration_ms: duration_ms(
+                actual_samples as usize,
+                spec.sample_rate,
+                spec.channels,
+            ),
+            complete,
+            error,
+        })
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+/// Reads samples until the data runs out, returning how many were read and
+/// the error that stopped it early, if any.
+fn count_samples<S: hound::Sample, R: std::io::Read>(
+    reader: &mut hound::WavReader<R>,
+) -> (u64, Option<String>) {
+    let mut count = 0;
+    for sample in reader.samples::<S>() {
+        if let Err(e) = sample {
+            return (count, Some(e.to_string()));
+        }
+        count += 1;
+    }
+    (count, None)
+}
+
+#[derive(serde::Serialize, Clone)]
+pub struct ConcatResult {
+    path: String,
+    duration_ms: u64,
+}
+
+/// Joins WAV recordings end to end into `output`, a new file in the
+/// recordings directory, with a sidecar so it shows up in the history. Every
+/// input must share the first one's sample rate, channel count and sample
+/// format, which the output keeps.
+#[tauri::command]
+pub fn concat_recordings(
+    app: AppHandle,
+    paths: Vec<String>,
+    output: String,
+) -> Result<ConcatResult, String> {
+    if paths.is_empty() {
+        return Err("No recordings to concatenate".to_string());
+    }
+    let inputs = paths
+        .iter()
+        .map(|path| resolve_recording_path(&app, path))
+        .collect::<Result<Vec<_>, _>>()?;
+    let output = resolve_new_recording_path(&app, &output)?;
+    if OutputFormat::from_extension(
+        output
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .unwrap_or(""),
+    ) != Some(OutputFormat::Wav)
+    {
+        return Err(format!("{} must be a .wav file", output.display()));
+    }
+
+    let mut readers = Vec::with_capacity(inputs.len());
+    for path in &inputs {
+        let reader = hound::WavReader::open(path)
+            .map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+        readers.push((path, reader));
+    }
+    let spec = readers[0].1.spec();
+    for (path, reader) in &readers {
+        let other = reader.spec();
+        if other != spec {
+            return Err(format!(
+                "{} is {} Hz, {} channel(s) but {} is {} Hz, {} channel(s)",
+                path.display(),
+                other.sample_rate,
+                other.channels,
+                inputs[0].display(),
+                spec.sample_rate,
+                spec.channels
+            ));
+        }
+    }
+
+    let mut writer = create_wav_writer(&output, spec)?;
+    let peak = match spec.sample_format {
+        hound::SampleFormat::Int => {
+            let scale = (1i64 << (spec.bits_per_sample.max(1) - 1)) as f32;
+            append_samples(readers, &mut writer, |sample: i32| sample as f32 / scale)?
+        }
+        hound::SampleFormat::Float => append_samples(readers, &mut writer, |sample: f32| sample)?,
+    };
+    let sample_count = writer.len() as usize;
+    writer.finalize().map_err(|e| e.to_string())?;
+
+    let duration_ms = duration_ms(sample_count, spec.sample_rate, spec.channels);
+    let first = read_sidecar(&inputs[0]);
+    let metadata = RecordingMetadata {
+        sample_rate: spec.sample_rate,
+        channels: spec.channels,
+        duration_ms,
+        device_name: first
+            .as_ref()
+            .and_then(|metadata| metadata.device_name.clone()),
+        created_at: unix_millis()?,
+        peak_dbfs: audio::linear_to_dbfs(peak.min(1.0)),
+        integrated_lufs: None,
+        format: OutputFormat::Wav,
+        normalization_gain: None,
+        denoised: false,
+        bits_per_sample: spec.bits_per_sample,
+        mid_side: first.as_ref().is_some_and(|metadata| metadata.mid_side),
+        tags: Vec::new(),
+        note: None,
+        location: None,
+        country: None,
+        keep: false,
+    };
+    write_sidecar(&output, &metadata)?;
+
+    Ok(ConcatResult {
+        path: output.to_string_lossy().to_string(),
+        duration_ms,
+    })
+}
+
+/// Copies every sample of `readers`, in order, to `writer` unchanged and
+/// returns the peak, measured through `normalize`.
+fn append_samples<S: hound::Sample + Copy>(
+    readers: Vec<(&PathBuf, hound::WavReader<BufReader<fs::File>>)>,
+    writer: &mut FileWavWriter,
+    normalize: impl Fn(S) -> f32,
+) -> Result<f32, String> {
+    let mut peak = 0.0f32;
+    for (path, mut reader) in readers {
+        for sample in reader.samples::<S>() {
+            let sample = sample.map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+            peak = peak.max(normalize(sample).abs());
+            writer.write_sample(sample).map_err(|e| e.to_string())?;
+        }
+    }
+    Ok(peak)
+}
+
+/// Writes each channel of a multi-channel WAV recording to its own mono WAV
+/// next to it, named `<name>_ch1.wav`, `<name>_ch2.wav` and so on, and returns
+/// the new paths. Samples are copied unchanged. The source's sidecar, if any,
+/// is copied to each output with the channel's own peak.
+#[tauri::command]
+pub fn split_channels(app: AppHandle, path: String) -> Result<Vec<String>, String> {
+    let path = resolve_recording_path(&app, &path)?;
+    if OutputFormat::from_extension(path.extension().and_then(|ext| ext.to_str()).unwrap_or(""))
+        != Some(OutputFormat::Wav)
+    {
+        return Err(format!("{} is not a WAV recording", path.display()));
+    }
+    let mut reader = hound::WavReader::open(&path)
+        .map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+    let spec = reader.spec();
+    if spec.channels < 2 {
+        return Err(format!(
+            "{} is already mono; there are no channels to split",
+            path.display()
+        ));
+    }
+
+    let stem = path
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .ok_or_else(|| format!("{} has no usable file name", path.display()))?;
+    let outputs = (1..=spec.channels)
+        .map(|channel| {
+            let name = path.with_file_name(format!("{}_ch{}.wav", stem, channel));
+            resolve_new_recording_path(&app, &name.to_string_lossy())
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let read_error = |e: hound::Error| format!("Failed to read {}: {}", path.display(), e);
+    let written = match spec.sample_format {
+        hound::SampleFormat::Int => {
+            let samples = reader
+                .samples::<i32>()
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(read_error)?;
+            let scale = (1i64 << (spec.bits_per_sample.max(1) - 1)) as f32;
+            write_channels(&samples, spec, &outputs, |sample| sample as f32 / scale)
+        }
+        hound::SampleFormat::Float => {
+            let samples = reader
+                .samples::<f32>()
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(read_error)?;
+            write_channels(&samples, spec, &outputs, |sample| sample)
+        }
+    };
+    let peaks = match written {
+        Ok(peaks) => peaks,
+        Err(e) => {
+            for output in &outputs {
+                let _ = fs::remove_file(output);
+            }
+            return Err(e);
+        }
+    };
+
+    if let Some(metadata) = read_sidecar(&path) {
+        for (output, peak) in outputs.iter().zip(peaks) {
+            let channel_metadata = RecordingMetadata {
+                channels: 1,
+                peak_dbfs: audio::linear_to_dbfs(peak.min(1.0)),
+                integrated_lufs: None,
+                mid_side: false,
+                ..metadata.clone()
+            };
+            write_sidecar(output, &channel_metadata)?;
+        }
+    }
+    info!(
+        "Split {} into {} channel files",
+        path.display(),
+        outputs.len()
+    );
+    Ok(outputs
+        .iter()
+        .map(|output| output.to_string_lossy().to_string())
+        .collect())
+}
+
+/// Interleaves two mono WAV recordings into a new 16-bit stereo WAV at
+/// `output` in the recordings directory, and returns its path. The input with
+/// the lower sample rate is resampled to the other's, and the shorter one is
+/// padded with silence.
+#[tauri::command]
+pub fn merge_to_stereo(
+    app: AppHandle,
+    left: String,
+    right: String,
+    output: String,
+) -> Result<String, String> {
+    let left = resolve_recording_path(&app, &left)?;
+    let right = resolve_recording_path(&app, &right)?;
+    let output = resolve_new_recording_path(&app, &output)?;
+    if OutputFormat::from_extension(
+        output
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .unwrap_or(""),
+    ) != Some(OutputFormat::Wav)
+    {
+        return Err(format!("{} must be a .wav file", output.display()));
+    }
+
+    let mut sides = Vec::with_capacity(2);
+    for path in [&left, &right] {
+        let wav = audio::read_wav(path)?;
+        if wav.channels != 1 {
+            return Err(format!(
+                "{} has {} channels; only mono recordings can be merged",
+                path.display(),
+                wav.channels
+            ));
+        }
+        sides.push(wav);
+    }
+    let sample_rate = sides.iter().map(|wav| wav.sample_rate).max().unwrap_or(0);
+    for wav in &mut sides {
+        if wav.sample_rate != sample_rate {
+            wav.samples = audio::resample_linear(&wav.samples, wav.sample_rate, sample_rate);
+            wav.sample_rate = sample_rate;
+        }
+    }
+    let frames = sides.iter().map(|wav| wav.samples.len()).max().unwrap_or(0);
+    let mut samples = Vec::with_capacity(frames * 2);
+    for frame in 0..frames {
+        for wav in &sides {
+            samples.push(to_i16(wav.samples.get(frame).copied().unwrap_or(0.0)));
+        }
+    }
+
+    write_audio_file(&output, &samples, sample_rate, 2, OutputFormat::Wav)?;
+    write_sidecar(&output, &derived_metadata(&samples, sample_rate, 2)?)?;
+    info!(
+        "Merged {} and {} into {}",
+        left.display(),
+        right.display(),
+        output.display()
+    );
+    Ok(output.to_string_lossy().to_string())
+}
+
+/// Sidecar for a 16-bit WAV made from other recordings rather than captured,
+/// so it shows up in the history like one.
+fn derived_metadata(
+    samples: &[i16],
+    sample_rate: u32,
+    channels: u16,
+) -> Result<RecordingMetadata, String> {
+    let peak = samples
+        .iter()
+        .map(|&sample| (sample as i32).unsigned_abs())
+        .max()
+        .unwrap_or(0);
+    Ok(RecordingMetadata {
+        sample_rate,
+        channels,
+        duration_ms: duration_ms(samples.len(), sample_rate, channels),
+        device_name: None,
+        created_at: unix_millis()?,
+        peak_dbfs: audio::linear_to_dbfs((peak as f32 / i16::MAX as f32).min(1.0)),
+        integrated_lufs: recording_loudness(samples, channels, sample_rate, false),
+        format: OutputFormat::Wav,
+        normalization_gain: None,
+        denoised: false,
+        bits_per_sample: 16,
+        mid_side: false,
+        tags: Vec::new(),
+        note: None,
+        location: None,
+        country: None,
+        keep: false,
+    })
+}
+
+/// Converts a recording to 8 kHz mono G.711 μ-law, as telephony and IVR
+/// systems expect, and writes it as a WAV file at `output` in the recordings
+/// directory. Returns the new file's path.
+#[tauri::command]
+pub fn export_ulaw(app: AppHandle, path: String, output: String) -> Result<String, String> {
+    let path = resolve_recording_path(&app, &path)?;
+    let output = resolve_new_recording_path(&app, &output)?;
+    if OutputFormat::from_extension(
+        output
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .unwrap_or(""),
+    ) != Some(OutputFormat::Wav)
+    {
+        return Err(format!("{} must be a .wav file", output.display()));
+    }
+
+    let wav = audio::read_wav(&path)?;
+    let mono = audio::downmix_to_mono(&wav.samples, wav.channels);
+    let resampled = audio::resample_linear(&mono, wav.sample_rate, audio::ULAW_SAMPLE_RATE);
+    let samples: Vec<i16> = resampled.into_iter().map(to_i16).collect();
+    audio::encode_ulaw_wav(&output, &samples, audio::ULAW_SAMPLE_RATE)?;
+    let metadata = RecordingMetadata {
+        bits_per_sample: 8,
+        ..derived_metadata(&samples, audio::ULAW_SAMPLE_RATE, 1)?
+    };
+    write_sidecar(&output, &metadata)?;
+    info!(
+        "Exported {} as μ-law to {}",
+        path.display(),
+        output.display()
+    );
+    Ok(output.to_string_lossy().to_string())
+}
+
+/// Writes every channel of `samples` to the matching mono file in `outputs`
+/// and returns each channel's peak, measured through `normalize`.
+fn write_channels<S: hound::Sample + Copy>(
+    samples: &[S],
+    spec: hound::WavSpec,
+    outputs: &[PathBuf],
+    normalize: impl Fn(S) -> f32,
+) -> Result<Vec<f32>, String> {
+    let mono = hound::WavSpec {
+        channels: 1,
+        ..spec
+    };
+    let mut peaks = Vec::with_capacity(outputs.len());
+    for (channel, output) in outputs.iter().enumerate() {
+        let mut writer = create_wav_writer(output, mono)?;
+        let mut peak = 0.0f32;
+        for &sample in samples.iter().skip(channel).step_by(spec.channels as usize) {
+            peak = peak.max(normalize(sample).abs());
+            writer.write_sample(sample).map_err(|e| e.to_string())?;
+        }
+        writer.finalize().map_err(|e| e.to_string())?;
+        peaks.push(peak);
+    }
+    Ok(peaks)
+}
+
+/// Replaces a recording's tags and note in its sidecar. Tags are trimmed,
+/// blanks dropped and duplicates removed, keeping the first occurrence; a
+/// blank note clears it.
+#[tauri::command]
+pub fn set_recording_tags(
+    app: AppHandle,
+    path: String,
+    tags: Vec<String>,
+    note: Option<String>,
+) -> Result<RecordingMetadata, String> {
+    let path = resolve_recording_path(&app, &path)?;
+    let mut metadata = read_sidecar(&path)
+        .ok_or_else(|| format!("{} has no readable metadata sidecar", path.display()))?;
+
+    let mut cleaned: Vec<String> = Vec::with_capacity(tags.len());
+    for tag in tags {
+        let tag = tag.trim();
+        if !tag.is_empty() && !cleaned.iter().any(|existing| existing == tag) {
+            cleaned.push(tag.to_string());
+        }
+    }
+    metadata.tags = cleaned;
+    metadata.note = note
+        .map(|note| note.trim().to_string())
+        .filter(|note| !note.is_empty());
+
+    write_sidecar(&path, &metadata)?;
+    Ok(metadata)
+}
+
+/// Canonicalizes `path` and checks that it lives inside the recordings
+/// directory, so the frontend can't be used to reach arbitrary files.
+pub fn resolve_recording_path(app: &AppHandle, path: &str) -> Result<PathBuf, String> {
+    let dir = recordings_dir(app)?;
+    let dir = dir.canonicalize().map_err(|e| {
+        format!(
+            "Recordings directory {} is unavailable: {}",
+            dir.display(),
+            e
+        )
+    })?;
+    let resolved = Path::new(path)
+        .canonicalize()
+        .map_err(|e| format!("Recording {} does not exist: {}", path, e))?;
+
+    if !resolved.starts_with(&dir) {
+        return Err(format!("{} is outside the recordings directory", path));
+    }
+    Ok(resolved)
+}
+
+/// Resolves a file name or path for a file that doesn't exist yet. Relative
+/// paths are taken from the recordings directory, and the result must stay
+/// inside it.
+pub fn resolve_new_recording_path(app: &AppHandle, path: &str) -> Result<PathBuf, String> {
+    let dir = recordings_dir(app)?;
+    let path = dir.join(path);
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| format!("{} is not a file path", path.display()))?;
+    let parent = path
+        .parent()
+        .ok_or_else(|| format!("{} is not a file path", path.display()))?;
+    let parent = parent
+        .canonicalize()
+        .map_err(|e| format!("Directory {} does not exist: {}", parent.display(), e))?;
+    let dir = dir.canonicalize().map_err(|e| {
+        format!(
+            "Recordings directory {} is unavailable: {}",
+            dir.display(),
+            e
+        )
+    })?;
+    if !parent.starts_with(&dir) {
+        return Err(format!(
+            "{} is outside the recordings directory",
+            path.display()
+        ));
+    }
+    let resolved = parent.join(file_name);
+    if resolved.exists() {
+        return Err(format!("{} already exists", resolved.display()));
+    }
+    Ok(resolved)
+}
+
+fn is_audio_file(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .and_then(OutputFormat::from_extension)
+        .is_some()
+}
+
+/// Parses the timestamp out of `event_searcher_recording_<millis>.<ext>` for
+/// any supported audio extension.
+fn recording_timestamp(path: &Path) -> Option<u64> {
+    OutputFormat::from_extension(path.extension()?.to_str()?)?;
+    let stem = path.file_stem()?.to_str()?;
+    stem.strip_prefix(RECORDING_FILE_PREFIX)?.parse().ok()
+}
+
+pub fn read_sidecar(recording_path: &Path) -> Option<RecordingMetadata> {
+    let contents = fs::read_to_string(sidecar_path(recording_path)).ok()?;
+    serde_json::from_str(&contents).ok()
+}
+
+/// Falls back to the WAV header for recordings without a sidecar.
+fn wav_duration_ms(path: &Path) -> Option<u64> {
+    let reader = hound::WavReader::open(path).ok()?;
+    let sample_rate = reader.spec().sample_rate;
+    if sample_rate == 0 {
+        return None;
+    }
+    Some(reader.duration() as u64 * 1000 / sample_rate as u64)
+}
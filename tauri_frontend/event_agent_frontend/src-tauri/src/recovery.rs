@@ -0,0 +1,164 @@
+//! Crash recovery for recordings held in memory.
+//!
+//! While such a recording runs, a background thread appends the new samples
+//! to `<name>.recovery.wav` every few seconds and rewrites its header, so a
+//! hard crash loses at most one interval. A clean stop or cancel deletes the
+//! file; one found at startup is announced with `recovery-available`.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::Ordering;
+use std::thread;
+use std::time::Duration;
+use tauri::{AppHandle, Emitter};
+use tracing::{error, info, warn};
+
+use crate::{
+    create_wav_writer, recorded_samples_from, recording_file_path, recordings_dir, wav_spec,
+    FileWavWriter, MutexExt, RecordingState,
+};
+
+/// Extension of recovery files. The extra dot keeps them out of
+/// `list_recordings`, whose timestamp parse rejects the `.recovery` stem.
+const RECOVERY_EXTENSION: &str = "recovery.wav";
+
+const CHECKPOINT_INTERVAL: Duration = Duration::from_secs(5);
+
+pub struct RecoveryFile {
+    path: PathBuf,
+    writer: FileWavWriter,
+    /// How many of the recorded samples are already in the file; see
+    /// `recorded_samples_from`.
+    written: usize,
+}
+
+/// Opens the recovery file for a recording that just started.
+pub fn open_recovery_file(
+    app: &AppHandle,
+    sample_rate: u32,
+    channels: u16,
+) -> Result<RecoveryFile, String> {
+    let path = recording_file_path(app, RECOVERY_EXTENSION)?;
+    let writer = create_wav_writer(&path, wav_spec(sample_rate, channels))?;
+    Ok(RecoveryFile {
+        path,
+        writer,
+        written: 0,
+    })
+}
+
+/// Starts the checkpoint thread for the recording that was just started. It
+/// exits once that recording stops or another one starts.
+pub fn spawn_checkpointer(state: &RecordingState) {
+    let generation = state.checkpoint_generation.fetch_add(1, Ordering::SeqCst) + 1;
+    let state = state.clone();
+    thread::spawn(move || loop {
+        thread::sleep(CHECKPOINT_INTERVAL);
+        if state.checkpoint_generation.load(Ordering::SeqCst) != generation
+            || state.stream.lock_or_recover().is_none()
+        {
+            break;
+        }
+        if let Err(e) = checkpoint(&state) {
+            warn!("recording checkpoint failed: {}", e);
+        }
+    });
+}
+
+/// Brings the on-disk copy up to date: appends new in-memory samples to the
+/// recovery file, or flushes the writer of a recording streamed to disk.
+pub fn checkpoint(state: &RecordingState) -> Result<(), String> {
+    if let Some(writer) = state.writer.lock_or_recover().as_mut() {
+        writer.flush().map_err(|e| e.to_string())?;
+    }
+    let mut recovery = state.recovery.lock_or_recover();
+    let Some(recovery) = recovery.as_mut() else {
+        return Ok(());
+    };
+    // Copy out first so the audio callback isn't held up by the disk.
+    let pending = recorded_samples_from(state, recovery.written);
+    for &sample in &pending {
+        recovery
+            .writer
+            .write_sample(sample)
+            .map_err(|e| e.to_string())?;
+    }
+    recovery.written += pending.len();
+    recovery.writer.flush().map_err(|e| e.to_string())
+}
+
+/// Path of the current recovery file, if the recording has one.
+pub fn recovery_path(state: &RecordingState) -> Option<PathBuf> {
+    state
+        .recovery
+        .lock_or_recover()
+        .as_ref()
+        .map(|recovery| recovery.path.clone())
+}
+
+/// Deletes the recovery file once the recording was saved or cancelled.
+pub fn discard_recovery_file(state: &RecordingState) {
+    let Some(recovery) = state.recovery.lock_or_recover().take() else {
+        return;
+    };
+    drop(recovery.writer);
+    if let Err(e) = fs::remove_file(&recovery.path) {
+        warn!(
+            "failed to remove recovery file {}: {}",
+            recovery.path.display(),
+            e
+        );
+    }
+}
+
+/// Whether `path` is a recovery file, live or left behind, rather than a
+/// saved recording.
+pub fn is_recovery_file(path: &Path) -> bool {
+    let suffix = format!(".{RECOVERY_EXTENSION}");
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .is_some_and(|name| name.ends_with(&suffix))
+}
+
+/// Recovery files left behind by earlier sessions.
+fn find_recovery_files(app: &AppHandle) -> Result<Vec<PathBuf>, String> {
+    let dir = recordings_dir(app)?;
+    let Ok(entries) = fs::read_dir(&dir) else {
+        return Ok(Vec::new());
+    };
+    let mut files: Vec<PathBuf> = entries
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| is_recovery_file(path))
+        .collect();
+    files.sort();
+    Ok(files)
+}
+
+/// Emits `recovery-available` with the path of each leftover recovery file.
+/// Called once at startup.
+pub fn announce_recovery_files(app: &AppHandle) {
+    let files = match find_recovery_files(app) {
+        Ok(files) => files,
+        Err(e) => {
+            error!("failed to look for recovery files: {}", e);
+            return;
+        }
+    };
+    for path in files {
+        info!(path = %path.display(), "Found an interrupted recording");
+        if let Err(e) = app.emit("recovery-available", path.to_string_lossy().to_string()) {
+            error!("failed to emit recovery-available: {:?}", e);
+        }
+    }
+}
+
+/// Leftover recovery files, for a UI that loaded after `recovery-available`
+/// was emitted.
+#[tauri::command]
+pub fn list_recovery_files(app: AppHandle) -> Result<Vec<String>, String> {
+    Ok(find_recovery_files(&app)?
+        .iter()
+        .map(|path| path.to_string_lossy().to_string())
+        .collect())
+}
@@ -0,0 +1,643 @@
+//! Local speech-to-text for finished recordings and, as partial transcripts,
+//! recordings in progress, backed by whisper.cpp.
+//!
+//! Whisper itself is only compiled in with the `whisper` cargo feature; without
+//! it the commands are still registered but report that transcription is
+//! unavailable.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc, Mutex};
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, Manager, State, Window};
+use tracing::{error, info, warn};
+
+use crate::{
+    audio, recording_snapshot, recordings, to_i16, write_audio_file, MutexExt, OutputFormat,
+    RecordingState,
+};
+
+/// Whisper models expect 16 kHz mono input.
+const WHISPER_SAMPLE_RATE: u32 = 16_000;
+
+/// Audio is fed to Whisper in chunks of this length (its context window), and
+/// cancellation is checked between them.
+#[cfg(feature = "whisper")]
+const WHISPER_CHUNK_SECS: usize = 30;
+
+/// Peak level of audio written by `prepare_for_transcription`.
+const PREPARED_PEAK_DBFS: f32 = -1.0;
+
+const TRANSCRIPTION_CANCELLED: &str = "Transcription cancelled";
+
+/// Minimum time between live transcription passes. A pass that takes longer
+/// pushes the next one back rather than queueing it.
+const PARTIAL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Length of the trailing audio each live pass transcribes. Consecutive
+/// windows overlap by all but `PARTIAL_INTERVAL`, so words cut off at the
+/// start of one window were complete in the one before.
+const PARTIAL_WINDOW_SECS: usize = 10;
+
+/// Cancellation flag shared with the transcription running on the blocking
+/// pool. Set by `cancel_transcription` and cleared when a new one starts;
+/// `transcribe_all` has its own.
+#[derive(Clone, Default)]
+pub struct TranscriptionState {
+    cancel: Arc<AtomicBool>,
+    // The live transcription following the current recording, if any.
+    live: Arc<Mutex<Option<LiveTranscription>>>,
+    // Set while `transcribe_all` works through its queue.
+    batch_running: Arc<AtomicBool>,
+    // Like `cancel`, but for the batch; set by `cancel_transcribe_all`.
+    batch_cancel: Arc<AtomicBool>,
+}
+
+#[derive(serde::Serialize, Clone)]
+struct TranscriptionReady {
+    path: String,
+    text: String,
+    segments: Vec<TranscriptSegment>,
+}
+
+/// Result of a transcription: the full text and Whisper's segments with their
+/// position in the audio, so the frontend can highlight text during playback.
+/// Saved next to the recording as `<name>.transcript.json`.
+#[derive(serde::Serialize, Clone, Default)]
+pub struct Transcript {
+    text: String,
+    segments: Vec<TranscriptSegment>,
+}
+
+#[derive(serde::Serialize, Clone)]
+struct TranscriptSegment {
+    text: String,
+    start_ms: u64,
+    end_ms: u64,
+}
+
+/// Payload of `partial-transcript`: the transcript of the last
+/// `PARTIAL_WINDOW_SECS` of the recording in progress.
+#[derive(serde::Serialize, Clone)]
+struct PartialTranscript {
+    text: String,
+}
+
+/// Payload of `transcribe-progress`, emitted as `transcribe_all` starts on
+/// each file. `index` counts from 1.
+#[derive(serde::Serialize, Clone)]
+struct TranscribeProgress {
+    index: usize,
+    total: usize,
+    path: String,
+}
+
+/// Payload of `transcribe-file-done`.
+#[derive(serde::Serialize, Clone)]
+struct TranscribeFileDone {
+    path: String,
+    transcript_path: String,
+    text: String,
+}
+
+/// Payload of `transcribe-file-error`.
+#[derive(serde::Serialize, Clone)]
+struct TranscribeFileError {
+    path: String,
+    message: String,
+}
+
+/// Payload of `transcribe-all-finished`.
+#[derive(serde::Serialize, Clone)]
+struct TranscribeAllFinished {
+    transcribed: usize,
+    total: usize,
+    cancelled: bool,
+}
+
+/// The saved recording, handed to the live transcription worker for its
+/// final pass.
+struct FinishedAudio {
+    path: String,
+    samples: Vec<i16>,
+    sample_rate: u32,
+    channels: u16,
+}
+
+/// Handle to the live transcription worker. Dropping it stops the worker
+/// without a final pass.
+pub struct LiveTranscription {
+    finished: mpsc::Sender<FinishedAudio>,
+}
+
+impl LiveTranscription {
+    /// Has the worker transcribe the whole saved recording and emit
+    /// `transcription-ready` for `path`, then stop.
+    pub fn finish(self, path: String, samples: &[i16], sample_rate: u32, channels: u16) {
+        let _ = self.finished.send(FinishedAudio {
+            path,
+            samples: samples.to_vec(),
+            sample_rate,
+            channels,
+        });
+    }
+}
+
+/// Detaches the live transcription from the recording that is ending.
+pub fn take_live_transcription(app: &AppHandle) -> Option<LiveTranscription> {
+    app.state::<TranscriptionState>()
+        .live
+        .lock_or_recover()
+        .take()
+}
+
+#[tauri::command]
+pub async fn transcribe_file(
+    window: Window,
+    state: State<'_, TranscriptionState>,
+    path: String,
+    model_path: String,
+) -> Result<Transcript, String> {
+    let cancel = state.cancel.clone();
+    cancel.store(false, Ordering::SeqCst);
+    let audio_path = path.clone();
+    let transcript = tauri::async_runtime::spawn_blocking(move || {
+        let samples = load_whisper_audio(Path::new(&audio_path))?;
+        Transcriber::new(&model_path)?.transcribe(&samples, &cancel)
+    })
+    .await
+    .map_err(|e| e.to_string())??;
+
+    let Some(transcript) = transcript else {
+        info!("Transcription of {} cancelled", path);
+        if let Err(e) = window.emit("transcription-cancelled", &path) {
+            error!("failed to emit transcription-cancelled: {:?}", e);
+        }
+        return Err(TRANSCRIPTION_CANCELLED.to_string());
+    };
+
+    // The transcript is still returned when the files can't be written, e.g.
+    // next to a recording in a read-only folder.
+    if let Err(e) = write_transcript(Path::new(&path), &transcript) {
+        warn!("could not save the transcript of {}: {}", path, e);
+    }
+    let ready = TranscriptionReady {
+        path,
+        text: transcript.text.clone(),
+        segments: transcript.segments.clone(),
+    };
+    if let Err(e) = window.emit("transcription-ready", ready) {
+        error!("failed to emit transcription-ready: {:?}", e);
+    }
+    Ok(transcript)
+}
+
+/// Transcribes every WAV recording that has no `.txt` transcript yet, one
+/// after another on a background worker, writing each transcript next to its
+/// recording. Returns once the model is loaded and the files are queued.
+///
+/// Emits `transcribe-progress` before each file, `transcribe-file-done` or
+/// `transcribe-file-error` after it, and `transcribe-all-finished` at the end.
+/// `cancel_transcribe_all` stops the batch, abandoning the file in progress.
+#[tauri::command]
+pub async fn transcribe_all(
+    app: AppHandle,
+    state: State<'_, TranscriptionState>,
+    model_path: String,
+) -> Result<(), String> {
+    let queue: Vec<PathBuf> = recordings::recording_paths(&app)?
+        .into_iter()
+        .filter(|path| {
+            OutputFormat::from_extension(
+                path.extension().and_then(|ext| ext.to_str()).unwrap_or(""),
+            ) == Some(OutputFormat::Wav)
+        })
+        .filter(|path| !transcript_path(path).exists())
+        .collect();
+    if state.batch_running.swap(true, Ordering::SeqCst) {
+        return Err("A batch transcription is already running".to_string());
+    }
+    // Cleared before the model loads, so a cancel during the load counts.
+    state.batch_cancel.store(false, Ordering::SeqCst);
+    let transcriber =
+        match tauri::async_runtime::spawn_blocking(move || Transcriber::new(&model_path)).await {
+            Ok(Ok(transcriber)) => transcriber,
+            Ok(Err(e)) => {
+                state.batch_running.store(false, Ordering::SeqCst);
+                return Err(e);
+            }
+            Err(e) => {
+                state.batch_running.store(false, Ordering::SeqCst);
+                return Err(e.to_string());
+            }
+        };
+    if state.batch_cancel.load(Ordering::SeqCst) {
+        state.batch_running.store(false, Ordering::SeqCst);
+        info!("Batch transcription cancelled while loading the model");
+        let finished = TranscribeAllFinished {
+            transcribed: 0,
+            total: queue.len(),
+            cancelled: true,
+        };
+        if let Err(e) = app.emit("transcribe-all-finished", finished) {
+            error!("failed to emit transcribe-all-finished: {:?}", e);
+        }
+        return Ok(());
+    }
+
+    let state = state.inner().clone();
+    info!("Transcribing {} recordings", queue.len());
+    std::thread::spawn(move || {
+        run_batch_transcription(&app, &state, &transcriber, &queue);
+        state.batch_running.store(false, Ordering::SeqCst);
+    });
+    Ok(())
+}
+
+fn run_batch_transcription(
+    app: &AppHandle,
+    state: &TranscriptionState,
+    transcriber: &Transcriber,
+    queue: &[PathBuf],
+) {
+    let mut transcribed = 0;
+    let mut cancelled = false;
+    for (index, path) in queue.iter().enumerate() {
+        if state.batch_cancel.load(Ordering::SeqCst) {
+            cancelled = true;
+            break;
+        }
+        // Something else may have transcribed it since it was queued.
+        let transcript = transcript_path(path);
+        if transcript.exists() {
+            continue;
+        }
+        let file = path.to_string_lossy().to_string();
+        let progress = TranscribeProgress {
+            index: index + 1,
+            total: queue.len(),
+            path: file.clone(),
+        };
+        if let Err(e) = app.emit("transcribe-progress", progress) {
+            error!("failed to emit transcribe-progress: {:?}", e);
+        }
+
+        let result = load_whisper_audio(path)
+            .and_then(|samples| transcriber.transcribe(&samples, &state.batch_cancel))
+            .and_then(|result| match result {
+                Some(result) => write_transcript(path, &result).map(|()| Some(result.text)),
+                None => Ok(None),
+            });
+        match result {
+            Ok(Some(text)) => {
+                transcribed += 1;
+                let done = TranscribeFileDone {
+                    path: file,
+                    transcript_path: transcript.to_string_lossy().to_string(),
+                    text,
+                };
+                if let Err(e) = app.emit("transcribe-file-done", done) {
+                    error!("failed to emit transcribe-file-done: {:?}", e);
+                }
+            }
+            Ok(None) => {
+                cancelled = true;
+                break;
+            }
+            Err(message) => {
+                warn!("transcription of {} failed: {}", file, message);
+                let failed = TranscribeFileError {
+                    path: file,
+                    message,
+                };
+                if let Err(e) = app.emit("transcribe-file-error", failed) {
+                    error!("failed to emit transcribe-file-error: {:?}", e);
+                }
+            }
+        }
+    }
+
+    info!(
+        "Batch transcription finished: {} of {} transcribed{}",
+        transcribed,
+        queue.len(),
+        if cancelled { ", cancelled" } else { "" }
+    );
+    let finished = TranscribeAllFinished {
+        transcribed,
+        total: queue.len(),
+        cancelled,
+    };
+    if let Err(e) = app.emit("transcribe-all-finished", finished) {
+        error!("failed to emit transcribe-all-finished: {:?}", e);
+    }
+}
+
+/// Plain-text transcript written next to a recording.
+pub fn transcript_path(recording_path: &Path) -> PathBuf {
+    recording_path.with_extension("txt")
+}
+
+/// Timed transcript written next to a recording. `<name>.json` is already the
+/// recording's metadata sidecar.
+pub fn transcript_json_path(recording_path: &Path) -> PathBuf {
+    recording_path.with_extension("transcript.json")
+}
+
+/// Saves `transcript` next to the recording as `<name>.txt` and
+/// `<name>.transcript.json`.
+fn write_transcript(recording_path: &Path, transcript: &Transcript) -> Result<(), String> {
+    let json_path = transcript_json_path(recording_path);
+    let json = serde_json::to_string_pretty(transcript).map_err(|e| e.to_string())?;
+    fs::write(&json_path, json)
+        .map_err(|e| format!("Failed to write {}: {}", json_path.display(), e))?;
+    let text_path = transcript_path(recording_path);
+    fs::write(&text_path, &transcript.text)
+        .map_err(|e| format!("Failed to write {}: {}", text_path.display(), e))
+}
+
+/// Asks the running transcription to stop. Does nothing when none is running.
+#[tauri::command]
+pub fn cancel_transcription(state: State<TranscriptionState>) {
+    state.cancel.store(true, Ordering::SeqCst);
+}
+
+/// Asks the running `transcribe_all` to stop. Does nothing when none is
+/// running.
+#[tauri::command]
+pub fn cancel_transcribe_all(state: State<TranscriptionState>) {
+    state.batch_cancel.store(true, Ordering::SeqCst);
+}
+
+/// Transcribes the recording in progress every `PARTIAL_INTERVAL`, emitting
+/// `partial-transcript` whenever the text of the latest window changes. When
+/// the recording is saved, the whole file is transcribed once more and
+/// reported with `transcription-ready`; a cancelled recording just ends it.
+///
+/// Only in-memory recordings can be followed, as with `snapshot_recording`.
+#[tauri::command]
+pub async fn start_live_transcription(
+    app: AppHandle,
+    state: State<'_, TranscriptionState>,
+    recording: State<'_, RecordingState>,
+    model_path: String,
+) -> Result<(), String> {
+    recording_snapshot(&recording, Some(0))?;
+    let transcriber = tauri::async_runtime::spawn_blocking(move || Transcriber::new(&model_path))
+        .await
+        .map_err(|e| e.to_string())??;
+
+    let (sender, receiver) = mpsc::channel();
+    // Replacing an earlier worker's handle stops it.
+    *state.live.lock_or_recover() = Some(LiveTranscription { finished: sender });
+    std::thread::spawn(move || run_live_transcription(app, transcriber, receiver));
+    info!("Live transcription started");
+    Ok(())
+}
+
+fn run_live_transcription(
+    app: AppHandle,
+    transcriber: Transcriber,
+    finished: mpsc::Receiver<FinishedAudio>,
+) {
+    // Live passes are never cancelled partway; the worker stops between them.
+    let cancel = Arc::new(AtomicBool::new(false));
+    let recording = app.state::<RecordingState>();
+    let mut last_window = Vec::new();
+    let mut last_text = String::new();
+    let mut timeout = PARTIAL_INTERVAL;
+    loop {
+        let audio = match finished.recv_timeout(timeout) {
+            Ok(audio) => audio,
+            Err(mpsc::RecvTimeoutError::Disconnected) => break,
+            Err(mpsc::RecvTimeoutError::Timeout) => {
+                let frames =
+                    PARTIAL_WINDOW_SECS * *recording.sample_rate.lock_or_recover() as usize;
+                match recording_snapshot(&recording, Some(frames)) {
+                    // Nothing new while paused.
+                    Ok(window) if window.samples == last_window => {}
+                    Ok(window) => {
+                        let samples =
+                            whisper_input(&window.samples, window.sample_rate, window.channels);
+                        match transcriber.transcribe(&samples, &cancel) {
+                            Ok(Some(transcript)) if transcript.text != last_text => {
+                                if let Err(e) = app.emit(
+                                    "partial-transcript",
+                                    PartialTranscript {
+                                        text: transcript.text.clone(),
+                                    },
+                                ) {
+                                    error!("failed to emit partial-transcript: {:?}", e);
+                                }
+                                last_text = transcript.text;
+                            }
+                            Ok(_) => {}
+                            Err(e) => warn!("live transcription pass failed: {}", e),
+                        }
+                        last_window = window.samples;
+                    }
+                    // The recording is stopping; wait for it to be saved or
+                    // dropped.
+                    Err(_) => timeout = Duration::MAX,
+                }
+                continue;
+            }
+        };
+
+        let samples = whisper_input(&audio.samples, audio.sample_rate, audio.channels);
+        match transcriber.transcribe(&samples, &cancel) {
+            Ok(transcript) => {
+                if let Some(transcript) = &transcript {
+                    if let Err(e) = write_transcript(Path::new(&audio.path), transcript) {
+                        warn!("could not save the transcript of {}: {}", audio.path, e);
+                    }
+                }
+                let transcript = transcript.unwrap_or_default();
+                let ready = TranscriptionReady {
+                    path: audio.path,
+                    text: transcript.text,
+                    segments: transcript.segments,
+                };
+                if let Err(e) = app.emit("transcription-ready", ready) {
+                    error!("failed to emit transcription-ready: {:?}", e);
+                }
+            }
+            Err(e) => error!("final transcription of {} failed: {}", audio.path, e),
+        }
+        break;
+    }
+    info!("Live transcription stopped");
+}
+
+/// Converts any WAV file, integer or float, into what Whisper expects: 16 kHz
+/// mono, peak-normalized to `PREPARED_PEAK_DBFS`, written as 16-bit WAV to
+/// `output`. Returns the output path.
+#[tauri::command]
+pub async fn prepare_for_transcription(input: String, output: String) -> Result<String, String> {
+    // Only an existing output can be the input file.
+    if let (Ok(input_path), Ok(output_path)) = (
+        Path::new(&input).canonicalize(),
+        Path::new(&output).canonicalize(),
+    ) {
+        if input_path == output_path {
+            return Err("The output must not overwrite the input".to_string());
+        }
+    }
+
+    let (input_path, output_path) = (PathBuf::from(&input), PathBuf::from(&output));
+    tauri::async_runtime::spawn_blocking(move || {
+        let mut samples = load_whisper_audio(&input_path)?;
+        let peak = samples.iter().fold(0.0f32, |max, s| max.max(s.abs()));
+        if peak > 0.0 {
+            let gain = audio::dbfs_to_linear(PREPARED_PEAK_DBFS) / peak;
+            samples.iter_mut().for_each(|sample| *sample *= gain);
+        }
+        let samples: Vec<i16> = samples.into_iter().map(to_i16).collect();
+        write_audio_file(
+            &output_path,
+            &samples,
+            WHISPER_SAMPLE_RATE,
+            1,
+            OutputFormat::Wav,
+        )
+    })
+    .await
+    .map_err(|e| e.to_string())??;
+    info!("Prepared {} for transcription as {}", input, output);
+    Ok(output)
+}
+
+/// 16-bit samples in Whisper's input format.
+fn whisper_input(samples: &[i16], sample_rate: u32, channels: u16) -> Vec<f32> {
+    let samples: Vec<f32> = samples
+        .iter()
+        .map(|&sample| sample as f32 / i16::MAX as f32)
+        .collect();
+    let mono = audio::downmix_to_mono(&samples, channels);
+    audio::resample_linear(&mono, sample_rate, WHISPER_SAMPLE_RATE)
+}
+
+fn load_whisper_audio(path: &Path) -> Result<Vec<f32>, String> {
+    let wav = audio::read_wav(path)?;
+    let mono = audio::downmix_to_mono(&wav.samples, wav.channels);
+    Ok(audio::resample_linear(
+        &mono,
+        wav.sample_rate,
+        WHISPER_SAMPLE_RATE,
+    ))
+}
+
+/// A loaded Whisper model, kept around so live transcription doesn't reload it
+/// on every pass.
+#[cfg(feature = "whisper")]
+struct Transcriber {
+    context: whisper_rs::WhisperContext,
+}
+
+#[cfg(feature = "whisper")]
+impl Transcriber {
+    fn new(model_path: &str) -> Result<Self, String> {
+        use whisper_rs::{WhisperContext, WhisperContextParameters};
+
+        let context =
+            WhisperContext::new_with_params(model_path, WhisperContextParameters::default())
+                .map_err(|e| format!("Failed to load Whisper model {}: {}", model_path, e))?;
+        Ok(Transcriber { context })
+    }
+
+    /// Transcribes `samples` chunk by chunk. Returns `None` if `cancel` was set
+    /// before the last chunk finished.
+    fn transcribe(
+        &self,
+        samples: &[f32],
+        cancel: &Arc<AtomicBool>,
+    ) -> Result<Option<Transcript>, String> {
+        run_whisper(&self.context, samples, cancel)
+    }
+}
+
+#[cfg(feature = "whisper")]
+fn run_whisper(
+    context: &whisper_rs::WhisperContext,
+    samples: &[f32],
+    cancel: &Arc<AtomicBool>,
+) -> Result<Option<Transcript>, String> {
+    use whisper_rs::{FullParams, SamplingStrategy};
+
+    let mut state = context.create_state().map_err(|e| e.to_string())?;
+
+    let mut text = String::new();
+    let mut segments = Vec::new();
+    let chunks = samples.chunks(WHISPER_CHUNK_SECS * WHISPER_SAMPLE_RATE as usize);
+    for (index, chunk) in chunks.enumerate() {
+        let chunk_start_ms = (index * WHISPER_CHUNK_SECS * 1000) as u64;
+        if cancel.load(Ordering::SeqCst) {
+            return Ok(None);
+        }
+
+        let mut params = FullParams::new(SamplingStrategy::Greedy { best_of: 1 });
+        params.set_print_special(false);
+        params.set_print_progress(false);
+        params.set_print_realtime(false);
+        params.set_print_timestamps(false);
+        // Also lets whisper.cpp give up partway through a chunk.
+        let abort = cancel.clone();
+        params.set_abort_callback_safe(move || abort.load(Ordering::SeqCst));
+
+        if let Err(e) = state.full(params, chunk) {
+            if cancel.load(Ordering::SeqCst) {
+                return Ok(None);
+            }
+            return Err(e.to_string());
+        }
+
+        let count = state.full_n_segments().map_err(|e| e.to_string())?;
+        for segment in 0..count {
+            let segment_text = state
+                .full_get_segment_text(segment)
+                .map_err(|e| e.to_string())?;
+            text.push_str(&segment_text);
+            // Whisper times segments in centiseconds from the chunk start.
+            let start = state
+                .full_get_segment_t0(segment)
+                .map_err(|e| e.to_string())?;
+            let end = state
+                .full_get_segment_t1(segment)
+                .map_err(|e| e.to_string())?;
+            let segment_text = segment_text.trim();
+            if !segment_text.is_empty() {
+                segments.push(TranscriptSegment {
+                    text: segment_text.to_string(),
+                    start_ms: chunk_start_ms + start.max(0) as u64 * 10,
+                    end_ms: chunk_start_ms + end.max(0) as u64 * 10,
+                });
+            }
+        }
+    }
+    if cancel.load(Ordering::SeqCst) {
+        return Ok(None);
+    }
+    Ok(Some(Transcript {
+        text: text.trim().to_string(),
+        segments,
+    }))
+}
+
+#[cfg(not(feature = "whisper"))]
+struct Transcriber;
+
+#[cfg(not(feature = "whisper"))]
+impl Transcriber {
+    fn new(_model_path: &str) -> Result<Self, String> {
+        Err("Transcription is unavailable: built without the `whisper` feature".to_string())
+    }
+
+    fn transcribe(
+        &self,
+        _samples: &[f32],
+        _cancel: &Arc<AtomicBool>,
+    ) -> Result<Option<Transcript>, String> {
+        unreachable!("no Transcriber exists without the `whisper` feature")
+    }
+}
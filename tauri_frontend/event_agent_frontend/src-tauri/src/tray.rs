@@ -0,0 +1,95 @@
+//! System tray icon for using the recorder without opening the window.
+
+use tauri::menu::{Menu, MenuItem};
+use tauri::tray::{MouseButton, MouseButtonState, TrayIconBuilder, TrayIconEvent};
+use tauri::{AppHandle, Listener, Manager};
+use tracing::error;
+
+use crate::{start_background_recording, stop_background_recording};
+
+const TRAY_ID: &str = "main-tray";
+
+#[derive(serde::Deserialize)]
+struct RecordingStatePayload {
+    state: String,
+}
+
+pub fn setup_tray(app: &AppHandle) -> tauri::Result<()> {
+    let start = MenuItem::with_id(app, "start", "Start Recording", true, None::<&str>)?;
+    let stop = MenuItem::with_id(app, "stop", "Stop Recording", true, None::<&str>)?;
+    let show = MenuItem::with_id(app, "show", "Show Window", true, None::<&str>)?;
+    let menu = Menu::with_items(app, &[&start, &stop, &show])?;
+
+    let mut builder = TrayIconBuilder::with_id(TRAY_ID)
+        .tooltip(tooltip_for("idle"))
+        .menu(&menu)
+        .show_menu_on_left_click(false)
+        .on_menu_event(|app, event| match event.id().as_ref() {
+            "start" => start_background_recording(app),
+            "stop" => stop_background_recording(app),
+            "show" => show_window(app),
+            _ => {}
+        })
+        .on_tray_icon_event(|tray, event| {
+            if let TrayIconEvent::Click {
+                button: MouseButton::Left,
+                button_state: MouseButtonState::Up,
+                ..
+            } = event
+            {
+                toggle_window(tray.app_handle());
+            }
+        });
+    if let Some(icon) = app.default_window_icon() {
+        builder = builder.icon(icon.clone());
+    }
+    builder.build(app)?;
+
+    // Keep the tooltip in sync with the recorder through the same
+    // `recording-state` events the frontend listens to.
+    let handle = app.clone();
+    app.listen_any("recording-state", move |event| {
+        let Ok(payload) = serde_json::from_str::<RecordingStatePayload>(event.payload()) else {
+            return;
+        };
+        if let Some(tray) = handle.tray_by_id(TRAY_ID) {
+            if let Err(e) = tray.set_tooltip(Some(tooltip_for(&payload.state))) {
+                error!("failed to update tray tooltip: {:?}", e);
+            }
+        }
+    });
+
+    Ok(())
+}
+
+fn tooltip_for(state: &str) -> String {
+    match state {
+        "recording" => "Event Agent - recording".to_string(),
+        "paused" => "Event Agent - paused".to_string(),
+        _ => "Event Agent - idle".to_string(),
+    }
+}
+
+fn show_window(app: &AppHandle) {
+    if let Some(win) = app.get_webview_window("main") {
+        if let Err(e) = win.show() {
+            error!("failed to show window: {:?}", e);
+        }
+        if let Err(e) = win.set_focus() {
+            error!("failed to focus window: {:?}", e);
+        }
+    }
+}
+
+fn toggle_window(app: &AppHandle) {
+    let Some(win) = app.get_webview_window("main") else {
+        return;
+    };
+    if win.is_visible().unwrap_or(false) {
+        if let Err(e) = win.hide() {
+            error!("failed to hide window: {:?}", e);
+        }
+    } else {
+        show_window(app);
+    }
+}
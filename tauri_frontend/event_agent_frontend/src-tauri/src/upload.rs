@@ -0,0 +1,82 @@
+//! Sending finished recordings to a remote endpoint.
+
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use std::collections::HashMap;
+use std::path::Path;
+use tauri::AppHandle;
+use tracing::{info, warn};
+
+use crate::recordings::resolve_recording_path;
+use crate::sidecar_path;
+
+/// Multipart-uploads a recording, plus its sidecar when there is one, and
+/// returns the response body. The audio goes in the `audio` field and the
+/// sidecar in `metadata`.
+///
+/// Errors reaching the server start with "Upload failed", while responses with
+/// an error status start with "Upload rejected" and include the status and
+/// body, so the frontend can tell whether a retry is worthwhile. Headers that
+/// aren't valid HTTP fail up front with "Invalid header".
+#[tauri::command]
+pub async fn upload_recording(
+    app: AppHandle,
+    path: String,
+    url: String,
+    headers: HashMap<String, String>,
+) -> Result<String, String> {
+    let headers = header_map(&headers)?;
+    let path = resolve_recording_path(&app, &path)?;
+    let mut form = reqwest::multipart::Form::new().part("audio", file_part(&path).await?);
+    let sidecar = sidecar_path(&path);
+    if sidecar.exists() {
+        form = form.part("metadata", file_part(&sidecar).await?);
+    }
+
+    let client = reqwest::Client::new();
+    let request = client.post(&url).headers(headers).multipart(form);
+
+    info!("Uploading {} to {}", path.display(), url);
+    let response = request
+        .send()
+        .await
+        .map_err(|e| format!("Upload failed: could not reach {}: {}", url, e))?;
+    let status = response.status();
+    let body = response
+        .text()
+        .await
+        .map_err(|e| format!("Upload failed: could not read the response: {}", e))?;
+    if !status.is_success() {
+        warn!("Upload of {} rejected with {}", path.display(), status);
+        return Err(format!("Upload rejected with status {}: {}", status, body));
+    }
+    info!("Uploaded {}", path.display());
+    Ok(body)
+}
+
+fn header_map(headers: &HashMap<String, String>) -> Result<HeaderMap, String> {
+    let mut map = HeaderMap::with_capacity(headers.len());
+    for (name, value) in headers {
+        let name = HeaderName::from_bytes(name.as_bytes())
+            .map_err(|e| format!("Invalid header name {:?}: {}", name, e))?;
+        let value = HeaderValue::from_str(value)
+            .map_err(|e| format!("Invalid header value for {}: {}", name, e))?;
+        map.append(name, value);
+    }
+    Ok(map)
+}
+
+/// Streams the file from disk rather than reading it into memory.
+async fn file_part(path: &Path) -> Result<reqwest::multipart::Part, String> {
+    let mime = match path.extension().and_then(|ext| ext.to_str()) {
+        Some("wav") => "audio/wav",
+        Some("flac") => "audio/flac",
+        Some("opus" | "ogg") => "audio/ogg",
+        Some("json") => "application/json",
+        _ => "application/octet-stream",
+    };
+    reqwest::multipart::Part::file(path)
+        .await
+        .map_err(|e| format!("Failed to open {}: {}", path.display(), e))?
+        .mime_str(mime)
+        .map_err(|e| e.to_string())
+}
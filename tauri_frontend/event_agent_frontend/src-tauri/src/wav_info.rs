@@ -0,0 +1,128 @@
+//! RIFF INFO metadata embedded in saved WAV files, so they describe themselves
+//! in tools that never see the sidecar JSON.
+
+use std::fs::OpenOptions;
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+use crate::filename::utc_from_millis;
+
+/// Fields written to the `LIST`/`INFO` chunk. Empty ones are left out.
+#[derive(Default)]
+pub struct WavInfo {
+    /// Milliseconds since the Unix epoch, written as `ICRD` (YYYY-MM-DD, UTC).
+    pub created_at: Option<u64>,
+    /// Written as `ICMT`, the comment field most players show.
+    pub description: Option<String>,
+    /// INFO has no dedicated location field; `IARL` (archival location) is the
+    /// one tools conventionally use for it.
+    pub location: Option<String>,
+}
+
+impl WavInfo {
+    fn entries(&self) -> Vec<([u8; 4], String)> {
+        let mut entries = Vec::new();
+        if let Some(created_at) = self.created_at {
+            let (year, month, day, ..) = utc_from_millis(created_at);
+            entries.push((*b"ICRD", format!("{year:04}-{month:02}-{day:02}")));
+        }
+        for (id, value) in [(*b"ICMT", &self.description), (*b"IARL", &self.location)] {
+            if let Some(value) = value.as_deref().map(str::trim).filter(|v| !v.is_empty()) {
+                entries.push((id, value.to_string()));
+            }
+        }
+        entries
+    }
+}
+
+/// Appends a `LIST`/`INFO` chunk to a finalized WAV file and fixes up the RIFF
+/// size. Does nothing when `info` has no fields set.
+pub fn append_info_chunk(path: &Path, info: &WavInfo) -> Result<(), String> {
+    let entries = info.entries();
+    if entries.is_empty() {
+        return Ok(());
+    }
+
+    let mut list = b"INFO".to_vec();
+    for (id, value) in entries {
+        // Values are NUL-terminated and every chunk is padded to an even size.
+        let mut data = value.into_bytes();
+        data.retain(|&b| b != 0);
+        data.push(0);
+        list.extend_from_slice(&id);
+        list.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        let padded = data.len() % 2 == 1;
+        list.extend(data);
+        if padded {
+            list.push(0);
+        }
+    }
+
+    let open_error = |e: std::io::Error| format!("Failed to update {}: {}", path.display(), e);
+    let mut file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(path)
+        .map_err(open_error)?;
+    let mut header = [0u8; 12];
+    file.read_exact(&mut header).map_err(open_error)?;
+    if &header[0..4] != b"RIFF" || &header[8..12] != b"WAVE" {
+        return Err(format!("{} is not a RIFF WAVE file", path.display()));
+    }
+
+    let mut end = file.seek(SeekFrom::End(0)).map_err(open_error)?;
+    let mut chunk = Vec::with_capacity(list.len() + 9);
+    if end % 2 == 1 {
+        chunk.push(0);
+    }
+    chunk.extend_from_slice(b"LIST");
+    chunk.extend_from_slice(&(list.len() as u32).to_le_bytes());
+    chunk.extend(list);
+    end += chunk.len() as u64;
+    let riff_size = u32::try_from(end - 8)
+        .map_err(|_| format!("{} is too large for a RIFF INFO chunk", path.display()))?;
+
+    file.write_all(&chunk).map_err(open_error)?;
+    file.seek(SeekFrom::Start(4)).map_err(open_error)?;
+    file.write_all(&riff_size.to_le_bytes())
+        .map_err(open_error)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn info_chunk_keeps_the_wav_readable() {
+        let path = std::env::temp_dir().join(format!("wav_info_{}.wav", std::process::id()));
+        let spec = hound::WavSpec {
+            channels: 1,
+            sample_rate: 8_000,
+            bits_per_sample: 16,
+            sample_format: hound::SampleFormat::Int,
+        };
+        let mut writer = hound::WavWriter::create(&path, spec).unwrap();
+        for sample in [0i16, 1000, -1000] {
+            writer.write_sample(sample).unwrap();
+        }
+        writer.finalize().unwrap();
+
+        let info = WavInfo {
+            created_at: Some(0),
+            // Odd lengths once NUL-terminated, so both need a pad byte.
+            description: Some("a note".to_string()),
+            location: Some("Oslo".to_string()),
+        };
+        append_info_chunk(&path, &info).unwrap();
+
+        let file = std::fs::read(&path).unwrap();
+        let riff_size = u32::from_le_bytes(file[4..8].try_into().unwrap());
+        assert_eq!(riff_size as usize, file.len() - 8);
+        assert!(file.windows(4).any(|id| id == b"ICMT"));
+        let mut reader = hound::WavReader::open(&path).unwrap();
+        let samples: Vec<i16> = reader.samples().map(Result::unwrap).collect();
+        assert_eq!(samples, [0, 1000, -1000]);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -223,7 +223,7 @@ export default function MinimalRecorder() {
     setStatusMessage("⏳ Processing audio...");
 
     try {
-      const filePath = await invoke<string>("stop_recording");
+      const { path: filePath } = await invoke<{ path: string }>("stop_recording");
       console.log("Recording stopped, file at:", filePath);
 
       if (filePath) {